@@ -0,0 +1,575 @@
+//! Reading `.zip`, `.tar` and `.tar.gz` archives without extracting them, and extracting
+//! received archives.
+
+use axum::body::Bytes;
+use flate2::read::GzDecoder;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, Read, Write},
+    path::{Component, Path, PathBuf},
+};
+use tokio::sync::mpsc;
+use zip::ZipArchive;
+
+/// Members beyond this number are not listed.
+pub const MAX_LISTED_ENTRIES: usize = 10_000;
+
+/// Size of the chunks a member is streamed in.
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    /// Detects the archive format by file name.
+    pub fn detect(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+
+        if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+/// A file or directory stored in an archive.
+#[derive(Serialize, Debug)]
+pub struct ArchiveEntry {
+    /// Path inside the archive with `/` separators.
+    pub path: String,
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+/// Lists up to [`MAX_LISTED_ENTRIES`] members. The flag is `true` if there were more.
+pub fn list(path: &Path, format: ArchiveFormat) -> io::Result<(Vec<ArchiveEntry>, bool)> {
+    let mut entries = Vec::new();
+    let mut truncated = false;
+
+    match format {
+        ArchiveFormat::Zip => {
+            let mut archive = ZipArchive::new(BufReader::new(File::open(path)?))?;
+            truncated = archive.len() > MAX_LISTED_ENTRIES;
+
+            for index in 0..archive.len().min(MAX_LISTED_ENTRIES) {
+                let member = archive.by_index(index)?;
+                entries.push(ArchiveEntry {
+                    path: member.name().trim_end_matches('/').to_string(),
+                    size: member.size(),
+                    is_dir: member.is_dir(),
+                });
+            }
+        }
+        ArchiveFormat::Tar | ArchiveFormat::TarGz => {
+            let mut archive = tar::Archive::new(open_tar(path, format)?);
+
+            for entry in archive.entries()? {
+                if entries.len() == MAX_LISTED_ENTRIES {
+                    truncated = true;
+                    break;
+                }
+
+                let entry = entry?;
+                entries.push(ArchiveEntry {
+                    path: tar_entry_path(&entry)?,
+                    size: entry.size(),
+                    is_dir: entry.header().entry_type().is_dir(),
+                });
+            }
+        }
+    }
+
+    Ok((entries, truncated))
+}
+
+fn open_tar(path: &Path, format: ArchiveFormat) -> io::Result<Box<dyn Read + Send>> {
+    let file = BufReader::new(File::open(path)?);
+
+    Ok(match format {
+        ArchiveFormat::TarGz => Box::new(GzDecoder::new(file)),
+        _ => Box::new(file),
+    })
+}
+
+fn tar_entry_path<R: Read>(entry: &tar::Entry<'_, R>) -> io::Result<String> {
+    Ok(entry
+        .path()?
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/"))
+}
+
+/// Finds a member and calls `read` with its size and content reader.
+pub fn with_member<T>(
+    path: &Path,
+    format: ArchiveFormat,
+    member: &str,
+    read: impl FnOnce(u64, &mut dyn Read) -> io::Result<T>,
+) -> io::Result<T> {
+    let not_found = || io::Error::new(io::ErrorKind::NotFound, "archive member not found");
+
+    match format {
+        ArchiveFormat::Zip => {
+            let mut archive = ZipArchive::new(BufReader::new(File::open(path)?))?;
+            let mut file = archive.by_name(member).map_err(|_| not_found())?;
+            if file.is_dir() {
+                return Err(not_found());
+            }
+            read(file.size(), &mut file)
+        }
+        ArchiveFormat::Tar | ArchiveFormat::TarGz => {
+            let mut archive = tar::Archive::new(open_tar(path, format)?);
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                if entry.header().entry_type().is_file() && tar_entry_path(&entry)? == member {
+                    return read(entry.size(), &mut entry);
+                }
+            }
+            Err(not_found())
+        }
+    }
+}
+
+/// Streams a member to `tx` in chunks. Runs on a blocking thread.
+///
+/// The member size is sent through `size_tx` before the content, or an error if
+/// the member couldn't be opened.
+pub fn stream_member(
+    path: &Path,
+    format: ArchiveFormat,
+    member: &str,
+    size_tx: tokio::sync::oneshot::Sender<io::Result<u64>>,
+    tx: mpsc::Sender<io::Result<Bytes>>,
+) {
+    let mut size_tx = Some(size_tx);
+
+    let result = with_member(path, format, member, |size, reader| {
+        if let Some(size_tx) = size_tx.take() {
+            let _ = size_tx.send(Ok(size));
+        }
+
+        let mut buf = vec![0; STREAM_CHUNK_SIZE];
+        loop {
+            let len = reader.read(&mut buf)?;
+            if len == 0 {
+                return Ok(());
+            }
+            if tx
+                .blocking_send(Ok(Bytes::copy_from_slice(&buf[..len])))
+                .is_err()
+            {
+                // Client disconnected.
+                return Ok(());
+            }
+        }
+    });
+
+    if let Err(err) = result {
+        match size_tx.take() {
+            Some(size_tx) => {
+                let _ = size_tx.send(Err(err));
+            }
+            None => {
+                tracing::warn!("Failed to stream archive member {member:?}: {err}");
+                let _ = tx.blocking_send(Err(err));
+            }
+        }
+    }
+}
+
+/// Bounds of an extraction, guarding against archive bombs.
+pub struct ExtractLimits {
+    /// Maximum total size of the extracted files in bytes.
+    pub max_size: u64,
+    /// Maximum number of extracted files and directories.
+    pub max_entries: usize,
+}
+
+/// Decides which archive members are extracted.
+pub trait MemberFilter {
+    /// Checks the member path before its content is read.
+    fn accepts_name(&self, name: &str) -> bool;
+
+    /// Checks the first chunk of the member's content before anything is written.
+    fn accepts_content(&self, name: &str, head: &[u8]) -> bool;
+}
+
+/// A file written by an extraction.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ExtractedFile {
+    /// Path relative to the destination directory with `/` separators.
+    pub path: String,
+    pub size: u64,
+    /// Hex SHA-256 of the content.
+    pub sha256: String,
+}
+
+/// What an extraction wrote.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Extracted {
+    pub files: Vec<ExtractedFile>,
+    pub size: u64,
+    /// Members left out by the [`MemberFilter`].
+    pub skipped: usize,
+}
+
+/// Extracts the files and directories of the archive into `dest`, which must exist.
+///
+/// Members whose path would leave `dest` fail the extraction, links, special files and
+/// members the `filter` rejects are skipped. The sizes written are counted as the data
+/// is decompressed, not taken from the headers, and `account` is called with each chunk
+/// before it is written. Runs on a blocking thread.
+pub fn extract(
+    path: &Path,
+    format: ArchiveFormat,
+    dest: &Path,
+    limits: &ExtractLimits,
+    filter: &dyn MemberFilter,
+    account: &mut dyn FnMut(u64) -> io::Result<()>,
+) -> io::Result<Extracted> {
+    let mut extractor = Extractor {
+        dest,
+        limits,
+        filter,
+        account,
+        entries: 0,
+        extracted: Extracted::default(),
+    };
+
+    match format {
+        ArchiveFormat::Zip => {
+            let mut archive = ZipArchive::new(BufReader::new(File::open(path)?))?;
+            if archive.len() > limits.max_entries {
+                return Err(extractor.too_many_entries());
+            }
+
+            for index in 0..archive.len() {
+                let mut member = archive.by_index(index)?;
+                if member.is_dir() {
+                    extractor.dir(member.name())?;
+                } else if member.is_file() && !member.is_symlink() {
+                    let name = member.name().to_owned();
+                    extractor.file(&name, &mut member)?;
+                }
+            }
+        }
+        ArchiveFormat::Tar | ArchiveFormat::TarGz => {
+            let mut archive = tar::Archive::new(open_tar(path, format)?);
+
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                let entry_type = entry.header().entry_type();
+                let name = tar_entry_path(&entry)?;
+                if entry_type.is_dir() {
+                    extractor.dir(&name)?;
+                } else if entry_type.is_file() {
+                    extractor.file(&name, &mut entry)?;
+                }
+            }
+        }
+    }
+
+    Ok(extractor.extracted)
+}
+
+struct Extractor<'a> {
+    dest: &'a Path,
+    limits: &'a ExtractLimits,
+    filter: &'a dyn MemberFilter,
+    account: &'a mut dyn FnMut(u64) -> io::Result<()>,
+    entries: usize,
+    extracted: Extracted,
+}
+
+impl Extractor<'_> {
+    fn dir(&mut self, name: &str) -> io::Result<()> {
+        let relative = self.member_path(name)?;
+        fs::create_dir_all(self.dest.join(relative))
+    }
+
+    fn file(&mut self, name: &str, reader: &mut dyn Read) -> io::Result<()> {
+        let relative = self.member_path(name)?;
+        let relative_name = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        if !self.filter.accepts_name(&relative_name) {
+            self.extracted.skipped += 1;
+            return Ok(());
+        }
+
+        let mut buf = vec![0; STREAM_CHUNK_SIZE];
+        let mut len = reader.read(&mut buf)?;
+        if !self.filter.accepts_content(&relative_name, &buf[..len]) {
+            self.extracted.skipped += 1;
+            return Ok(());
+        }
+
+        let path = self.dest.join(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Fails on duplicate members rather than overwriting what was already extracted
+        let mut file = File::options().write(true).create_new(true).open(&path)?;
+
+        let mut hasher = Sha256::new();
+        let mut size = 0;
+        while len > 0 {
+            size += len as u64;
+            self.extracted.size += len as u64;
+            if self.extracted.size > self.limits.max_size {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "the extracted files are larger than {} bytes",
+                        self.limits.max_size
+                    ),
+                ));
+            }
+            (self.account)(len as u64)?;
+            hasher.update(&buf[..len]);
+            file.write_all(&buf[..len])?;
+            len = reader.read(&mut buf)?;
+        }
+
+        self.extracted.files.push(ExtractedFile {
+            path: relative_name,
+            size,
+            sha256: crate::checksum::to_hex(&hasher.finalize()),
+        });
+        Ok(())
+    }
+
+    /// Counts the member and resolves its path relative to the destination.
+    fn member_path(&mut self, name: &str) -> io::Result<PathBuf> {
+        self.entries += 1;
+        if self.entries > self.limits.max_entries {
+            return Err(self.too_many_entries());
+        }
+
+        let relative = safe_member_path(name).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsafe path in archive: {name}"),
+            )
+        })?;
+        Ok(relative)
+    }
+
+    fn too_many_entries(&self) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "the archive has more than {} entries",
+                self.limits.max_entries
+            ),
+        )
+    }
+}
+
+/// Turns a member path into a relative path that can't leave the destination
+/// directory, or `None` if it is absolute or goes up with `..`.
+fn safe_member_path(name: &str) -> Option<PathBuf> {
+    if name.starts_with(['/', '\\']) {
+        return None;
+    }
+
+    let mut path = PathBuf::new();
+    for component in name.split(['/', '\\']) {
+        match component {
+            "" | "." => continue,
+            ".." => return None,
+            _ if component.chars().any(char::is_control) => return None,
+            _ => path.push(component),
+        }
+    }
+
+    // Catches drive prefixes like `C:` on Windows
+    let is_relative = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)));
+    (is_relative && !path.as_os_str().is_empty()).then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(
+            ArchiveFormat::detect(Path::new("a/b.ZIP")),
+            Some(ArchiveFormat::Zip)
+        );
+        assert_eq!(
+            ArchiveFormat::detect(Path::new("backup.tar.gz")),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(
+            ArchiveFormat::detect(Path::new("backup.tgz")),
+            Some(ArchiveFormat::TarGz)
+        );
+        assert_eq!(
+            ArchiveFormat::detect(Path::new("x.tar")),
+            Some(ArchiveFormat::Tar)
+        );
+        assert_eq!(ArchiveFormat::detect(Path::new("x.gz")), None);
+    }
+
+    #[test]
+    fn test_tar_list_and_read() {
+        let dir = TempDir::new();
+        let path = dir.join("archive.tar");
+        {
+            let mut builder = tar::Builder::new(File::create(&path).unwrap());
+            let mut header = tar::Header::new_gnu();
+            header.set_size(5);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, "docs/hello.txt", &b"hello"[..])
+                .unwrap();
+            builder.finish().unwrap();
+        }
+
+        let (entries, truncated) = list(&path, ArchiveFormat::Tar).unwrap();
+        assert!(!truncated);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, "docs/hello.txt");
+        assert_eq!(entries[0].size, 5);
+
+        let content = with_member(&path, ArchiveFormat::Tar, "docs/hello.txt", |_, reader| {
+            let mut content = String::new();
+            reader.read_to_string(&mut content)?;
+            Ok(content)
+        })
+        .unwrap();
+        assert_eq!(content, "hello");
+
+        assert!(with_member(&path, ArchiveFormat::Tar, "missing", |_, _| Ok(())).is_err());
+    }
+
+    #[test]
+    fn test_safe_member_path() {
+        assert_eq!(
+            safe_member_path("docs/./hello.txt"),
+            Some(PathBuf::from("docs").join("hello.txt"))
+        );
+        assert_eq!(
+            safe_member_path("docs\\10:30 notes.txt").is_some(),
+            cfg!(unix)
+        );
+        assert_eq!(safe_member_path("../etc/passwd"), None);
+        assert_eq!(safe_member_path("docs/../../x"), None);
+        assert_eq!(safe_member_path("/etc/passwd"), None);
+        assert_eq!(safe_member_path("\\server\\share"), None);
+        assert_eq!(safe_member_path("./"), None);
+    }
+
+    /// Leaves out `.exe` files and content starting with `MZ`.
+    struct NoExecutables;
+
+    impl MemberFilter for NoExecutables {
+        fn accepts_name(&self, name: &str) -> bool {
+            !name.ends_with(".exe")
+        }
+
+        fn accepts_content(&self, _name: &str, head: &[u8]) -> bool {
+            !head.starts_with(b"MZ")
+        }
+    }
+
+    #[test]
+    fn test_extract_limits() {
+        let dir = TempDir::new();
+        let path = dir.join("archive.tar");
+        {
+            let mut builder = tar::Builder::new(File::create(&path).unwrap());
+            for (name, content) in [
+                ("a/one.txt", &b"hello"[..]),
+                ("a/two.txt", b"hello"),
+                ("setup.exe", b"hello"),
+                ("b/renamed.txt", b"MZ..."),
+            ] {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(content.len() as u64);
+                header.set_mode(0o644);
+                header.set_cksum();
+                builder.append_data(&mut header, name, content).unwrap();
+            }
+            builder.finish().unwrap();
+        }
+
+        let mut accounted = 0;
+        let limits = ExtractLimits {
+            max_size: 10,
+            max_entries: 4,
+        };
+        let dest = dir.join("ok");
+        std::fs::create_dir(&dest).unwrap();
+        let extracted = extract(
+            &path,
+            ArchiveFormat::Tar,
+            &dest,
+            &limits,
+            &NoExecutables,
+            &mut |len| {
+                accounted += len;
+                Ok(())
+            },
+        )
+        .unwrap();
+        let hello_sha256 = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        assert_eq!(
+            extracted.files,
+            ["a/one.txt", "a/two.txt"].map(|path| ExtractedFile {
+                path: path.to_owned(),
+                size: 5,
+                sha256: hello_sha256.to_owned(),
+            })
+        );
+        assert_eq!((extracted.size, extracted.skipped), (10, 2));
+        assert_eq!(accounted, 10);
+        assert_eq!(std::fs::read(dest.join("a/two.txt")).unwrap(), b"hello");
+        assert!(!dest.join("setup.exe").exists());
+        assert!(!dest.join("b").exists());
+
+        for limits in [
+            ExtractLimits {
+                max_size: 9,
+                max_entries: 4,
+            },
+            ExtractLimits {
+                max_size: 10,
+                max_entries: 3,
+            },
+        ] {
+            let dest = dir.join("bomb");
+            let _ = std::fs::remove_dir_all(&dest);
+            std::fs::create_dir(&dest).unwrap();
+            let result = extract(
+                &path,
+                ArchiveFormat::Tar,
+                &dest,
+                &limits,
+                &NoExecutables,
+                &mut |_| Ok(()),
+            );
+            assert!(result.is_err());
+        }
+    }
+}
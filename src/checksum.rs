@@ -0,0 +1,194 @@
+//! File checksums for verifying transfers, in the format of `sha256sum` and friends.
+
+use crate::fs_object::FsObject;
+use md5::{Digest, Md5};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::{
+    borrow::Cow,
+    fmt::Write as _,
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+const READ_BUFFER_SIZE: usize = 256 * 1024;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgo {
+    #[default]
+    Sha256,
+    Blake3,
+    Md5,
+}
+
+impl HashAlgo {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HashAlgo::Sha256 => "sha256",
+            HashAlgo::Blake3 => "blake3",
+            HashAlgo::Md5 => "md5",
+        }
+    }
+
+    /// Conventional name of the checksum manifest, e.g. `SHA256SUMS`.
+    pub fn manifest_name(self) -> &'static str {
+        match self {
+            HashAlgo::Sha256 => "SHA256SUMS",
+            HashAlgo::Blake3 => "B3SUMS",
+            HashAlgo::Md5 => "MD5SUMS",
+        }
+    }
+}
+
+/// Streams the file through the hash function and returns the lowercase hex digest.
+pub fn hash_file(path: &Path, algo: HashAlgo) -> io::Result<String> {
+    let file = File::open(path)?;
+
+    let digest = match algo {
+        HashAlgo::Sha256 => {
+            let mut hasher = Sha256::new();
+            read_all(file, |data| hasher.update(data))?;
+            hasher.finalize().to_vec()
+        }
+        HashAlgo::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            read_all(file, |data| {
+                hasher.update(data);
+            })?;
+            hasher.finalize().as_bytes().to_vec()
+        }
+        HashAlgo::Md5 => {
+            let mut hasher = Md5::new();
+            read_all(file, |data| hasher.update(data))?;
+            hasher.finalize().to_vec()
+        }
+    };
+
+    Ok(to_hex(&digest))
+}
+
+fn read_all(mut reader: impl Read, mut update: impl FnMut(&[u8])) -> io::Result<()> {
+    let mut buf = vec![0; READ_BUFFER_SIZE];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => update(&buf[..n]),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+/// Returns the checksum of the file, using the SHA-256 cached in [`FsObject`] when available.
+pub fn file_checksum(fs_object: &FsObject, algo: HashAlgo) -> io::Result<String> {
+    if algo != HashAlgo::Sha256 {
+        return hash_file(&fs_object.path, algo);
+    }
+
+    if let Some(sha256) = fs_object.sha256.get() {
+        return Ok(sha256.clone());
+    }
+
+    let sha256 = hash_file(&fs_object.path, algo)?;
+    Ok(fs_object.sha256.get_or_init(|| sha256).clone())
+}
+
+/// Formats one manifest line as accepted by `sha256sum -c`.
+///
+/// Like coreutils, a path with a backslash or a line break is escaped and the line
+/// is prefixed with a backslash, so a file name can't add lines to the manifest.
+pub fn manifest_line(buf: &mut String, hash: &str, path: &str) {
+    let _ = match escape_manifest_path(path) {
+        Cow::Borrowed(path) => writeln!(buf, "{hash}  {path}"),
+        Cow::Owned(escaped) => writeln!(buf, "\\{hash}  {escaped}"),
+    };
+}
+
+/// Escapes the path as it appears in a manifest line.
+pub fn escape_manifest_path(path: &str) -> Cow<'_, str> {
+    if !path.contains(['\\', '\n', '\r']) {
+        return Cow::Borrowed(path);
+    }
+
+    Cow::Owned(
+        path.replace('\\', "\\\\")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r"),
+    )
+}
+
+/// Builds the checksum manifest of all files in the directory, with paths relative to it.
+///
+/// Unreadable files are left out with a warning.
+pub fn directory_manifest(dir: &FsObject, algo: HashAlgo) -> String {
+    let mut manifest = String::new();
+
+    let mut files: Vec<&FsObject> = dir.recursive_iter().filter(|item| item.is_file()).collect();
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    for file in files {
+        let Ok(relative) = file.path.strip_prefix(&dir.path) else {
+            continue;
+        };
+        let relative = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        match file_checksum(file, algo) {
+            Ok(hash) => manifest_line(&mut manifest, &hash, &relative),
+            Err(err) => tracing::warn!("Failed to hash {}: {err}", file.path.display()),
+        }
+    }
+
+    manifest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_hash_file() {
+        let dir = TempDir::new();
+        let path = dir.join("abc.txt");
+        std::fs::write(&path, b"abc").unwrap();
+
+        assert_eq!(
+            hash_file(&path, HashAlgo::Sha256).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hash_file(&path, HashAlgo::Md5).unwrap(),
+            "900150983cd24fb0d6963f7d28e17f72"
+        );
+        assert_eq!(
+            hash_file(&path, HashAlgo::Blake3).unwrap(),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+    }
+
+    #[test]
+    fn test_manifest_line() {
+        let mut manifest = String::new();
+        manifest_line(&mut manifest, "abc", "docs/a b.txt");
+        manifest_line(&mut manifest, "def", "x\nabc  forged\\.txt");
+        assert_eq!(
+            manifest,
+            "abc  docs/a b.txt\n\\def  x\\nabc  forged\\\\.txt\n"
+        );
+    }
+}
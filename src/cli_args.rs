@@ -1,3 +1,4 @@
+use crate::fs_object::{SizeUnits, SortKey, SortOptions};
 use clap::ArgGroup;
 use clap::Parser;
 use std::path::PathBuf;
@@ -8,7 +9,7 @@ use std::path::PathBuf;
 #[command(group(
     ArgGroup::new("mode")
         .required(true)
-        .args(&["receive", "paths"]),
+        .args(&["receive", "paths", "text"]),
 ))]
 pub struct Args {
     /// Set directories and files that will be distributed (only in transmitter mode)
@@ -19,6 +20,11 @@ pub struct Args {
     )]
     pub paths: Vec<PathBuf>,
 
+    /// Text shown on the page with a copy button, e.g. a URL; `-` reads it from the standard input
+    /// (only in transmitter mode)
+    #[arg(long, value_name = "TEXT", conflicts_with = "receive")]
+    pub text: Option<String>,
+
     /// Port number
     #[arg(short = 'p', long, default_value_t = 48666)]
     pub port: u16,
@@ -39,9 +45,126 @@ pub struct Args {
     /// Maximum total size of received files per request in MiB
     #[arg(long, short = 's', default_value_t = 50)]
     pub max_total_received_files_size: usize,
+
+    /// Maximum size of a single received file in MiB (only in receiver mode)
+    #[arg(long, value_name = "MiB", requires = "receive")]
+    pub max_file_size: Option<u64>,
+
+    /// Maximum total size of the received files directory in MiB (only in receiver mode)
+    #[arg(long, value_name = "MiB", requires = "receive")]
+    pub quota: Option<u64>,
+
+    /// Reject uploads that would leave less free disk space than this in MiB (only in receiver mode)
+    #[arg(long, value_name = "MiB", requires = "receive")]
+    pub min_free_space: Option<u64>,
+
+    /// Accept only files with these extensions, e.g. `pdf,docx` (only in receiver mode)
+    #[arg(long, value_name = "EXT", value_delimiter = ',', requires = "receive")]
+    pub allow_extensions: Vec<String>,
+
+    /// Reject files with these extensions, e.g. `exe,bat,sh` (only in receiver mode)
+    #[arg(long, value_name = "EXT", value_delimiter = ',', requires = "receive")]
+    pub deny_extensions: Vec<String>,
+
+    /// Accept only files whose content is of these MIME types, e.g. `application/pdf,image/*` (only in receiver mode)
+    #[arg(long, value_name = "MIME", value_delimiter = ',', requires = "receive")]
+    pub allow_mime_types: Vec<String>,
+
+    /// Reject files whose content is of these MIME types, e.g. `application/x-executable` (only in receiver mode)
+    #[arg(long, value_name = "MIME", value_delimiter = ',', requires = "receive")]
+    pub deny_mime_types: Vec<String>,
+
+    /// Shell command run for every received file, described in the MINICLOUD_FILE_PATH, MINICLOUD_FILE_NAME,
+    /// MINICLOUD_FILE_SIZE, MINICLOUD_FILE_SHA256 and MINICLOUD_CLIENT_IP environment variables (only in receiver mode)
+    #[arg(long, value_name = "CMD", requires = "receive")]
+    pub on_upload: Option<String>,
+
+    /// Stage received files in this directory and move them to the output directory only if
+    /// the --on-upload command exits with 0, e.g. for virus scanning
+    #[arg(long, value_name = "DIR", requires = "on_upload")]
+    pub quarantine_dir: Option<PathBuf>,
+
+    /// Require senders to enter their name and save their files to a subdirectory named after it (only in receiver mode)
+    #[arg(long, default_value_t = false, requires = "receive")]
+    pub ask_sender: bool,
+
+    /// Let senders pick one of these inboxes, e.g. `Group A,Group B`, and save their files to its subdirectory
+    /// (only in receiver mode)
+    #[arg(long, value_name = "NAME", value_delimiter = ',', requires = "receive")]
+    pub inboxes: Vec<String>,
+
+    /// Let uploaders download and delete their own files for this many minutes after uploading (only in receiver mode)
+    #[arg(long, value_name = "MINUTES", requires = "receive")]
+    pub uploader_access: Option<u64>,
+
+    /// Extract received `.zip`, `.tar` and `.tar.gz` archives into a subdirectory named after the archive
+    /// (only in receiver mode)
+    #[arg(long, default_value_t = false, requires = "receive")]
+    pub extract_archives: bool,
+
+    /// Keep received archives after extracting them instead of deleting them
+    #[arg(long, default_value_t = false, requires = "extract_archives")]
+    pub keep_archives: bool,
+
+    /// Maximum total size of the files extracted from one archive in MiB
+    #[arg(
+        long,
+        value_name = "MiB",
+        default_value_t = 1024,
+        requires = "extract_archives"
+    )]
+    pub max_extracted_size: u64,
+
+    /// Maximum number of files and directories extracted from one archive
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 10_000,
+        requires = "extract_archives"
+    )]
+    pub max_extracted_entries: usize,
+
+    /// Display sizes in SI units (kB, MB, GB) instead of binary ones (KiB, MiB, GiB)
+    #[arg(long, default_value_t = false)]
+    pub si: bool,
+
+    /// Default order of entries in the listing (only in transmitter mode)
+    #[arg(long, value_enum, default_value_t = SortKey::Name)]
+    pub sort: SortKey,
+
+    /// List directories before files (only in transmitter mode)
+    #[arg(long, default_value_t = false)]
+    pub dirs_first: bool,
+
+    /// Allow clients to search inside the shared text files (only in transmitter mode)
+    #[arg(long, default_value_t = false, conflicts_with = "receive")]
+    pub content_search: bool,
+
+    /// Files larger than this size in MiB are skipped by the content search
+    #[arg(long, default_value_t = 10, requires = "content_search")]
+    pub content_search_max_size: u64,
+
+    /// Compute SHA-256 checksums of all files at startup and show them in the listing (only in transmitter mode)
+    #[arg(long, default_value_t = false, conflicts_with = "receive")]
+    pub checksums: bool,
 }
 
 impl Args {
+    pub fn size_units(&self) -> SizeUnits {
+        if self.si {
+            SizeUnits::Decimal
+        } else {
+            SizeUnits::Binary
+        }
+    }
+
+    pub fn sort_options(&self) -> SortOptions {
+        SortOptions {
+            key: self.sort,
+            dirs_first: self.dirs_first,
+        }
+    }
+
     pub fn prepare_paths(&mut self) {
         self.paths.retain_mut(|path| match path.canonicalize() {
             Ok(canonicalized) => {
@@ -0,0 +1,240 @@
+//! Grep-like search inside the shared text files.
+
+use crate::fs_object::FsObject;
+use crate::search::{Matcher, SearchIndex};
+use serde::Serialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::{BufRead, BufReader, Result},
+    path::Path,
+    sync::Arc,
+};
+use tokio::sync::mpsc;
+
+/// Number of leading bytes inspected to tell binary files from text ones.
+const BINARY_SNIFF_LEN: usize = 8192;
+
+/// Maximum matches reported for a single file.
+const MAX_MATCHES_PER_FILE: usize = 100;
+
+/// Longer lines are cut to keep the responses small.
+const MAX_LINE_LEN: usize = 512;
+
+/// Limits applied to a content search request.
+#[derive(Clone, Copy, Debug)]
+pub struct GrepLimits {
+    /// Files larger than this are skipped.
+    pub max_file_size: u64,
+    /// Number of lines shown before and after every match.
+    pub context: usize,
+    /// The search stops after this many matches in total.
+    pub max_matches: usize,
+}
+
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct LineMatch {
+    /// 1-based line number.
+    pub line: usize,
+    pub text: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+/// All matches found in a single file, sent to the client as one NDJSON line.
+#[derive(Serialize)]
+pub struct FileMatches<'a> {
+    pub path: &'a str,
+    pub download: String,
+    pub matches: Vec<LineMatch>,
+}
+
+/// Returns `true` if the beginning of the data looks like a binary file.
+pub fn is_binary(head: &[u8]) -> bool {
+    head.contains(&0)
+}
+
+/// Searches a single file. Returns `Ok(None)` for binary files.
+pub fn grep_file(
+    path: &Path,
+    matcher: &Matcher,
+    context: usize,
+    max_matches: usize,
+) -> Result<Option<Vec<LineMatch>>> {
+    let mut reader = BufReader::with_capacity(BINARY_SNIFF_LEN, File::open(path)?);
+    if is_binary(reader.fill_buf()?) {
+        return Ok(None);
+    }
+
+    Ok(Some(grep_reader(reader, matcher, context, max_matches)?))
+}
+
+fn grep_reader(
+    mut reader: impl BufRead,
+    matcher: &Matcher,
+    context: usize,
+    max_matches: usize,
+) -> Result<Vec<LineMatch>> {
+    let mut matches: Vec<LineMatch> = Vec::new();
+    let mut before: VecDeque<String> = VecDeque::with_capacity(context);
+    // Indices of matches that still collect trailing context lines.
+    let mut pending_after: Vec<usize> = Vec::new();
+    let mut raw_line = Vec::new();
+    let mut line_number = 0;
+
+    loop {
+        raw_line.clear();
+        if reader.read_until(b'\n', &mut raw_line)? == 0 {
+            break;
+        }
+        line_number += 1;
+
+        let line = line_text(&raw_line);
+
+        pending_after.retain(|&index| {
+            let after = &mut matches[index].after;
+            after.push(line.clone());
+            after.len() < context
+        });
+
+        if matches.len() < max_matches && matcher.is_match(&line) {
+            matches.push(LineMatch {
+                line: line_number,
+                text: line.clone(),
+                before: before.iter().cloned().collect(),
+                after: Vec::new(),
+            });
+            if context > 0 {
+                pending_after.push(matches.len() - 1);
+            }
+        } else if matches.len() >= max_matches && pending_after.is_empty() {
+            break;
+        }
+
+        if context > 0 {
+            if before.len() == context {
+                before.pop_front();
+            }
+            before.push_back(line);
+        }
+    }
+
+    Ok(matches)
+}
+
+fn line_text(raw_line: &[u8]) -> String {
+    let trimmed = raw_line
+        .strip_suffix(b"\n")
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .unwrap_or(raw_line);
+
+    let mut text = String::from_utf8_lossy(trimmed).into_owned();
+    if text.len() > MAX_LINE_LEN {
+        let mut end = MAX_LINE_LEN;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push('…');
+    }
+    text
+}
+
+/// Walks all indexed files and sends NDJSON lines with matches to `tx`.
+///
+/// Runs on a blocking thread; stops early when the client disconnects.
+pub fn stream_matches(
+    index: Arc<SearchIndex>,
+    fs_objects: Arc<HashMap<u64, Arc<FsObject>>>,
+    matcher: Matcher,
+    limits: GrepLimits,
+    tx: mpsc::Sender<String>,
+) {
+    let mut remaining = limits.max_matches;
+
+    for entry in index.entries() {
+        if remaining == 0 {
+            break;
+        }
+
+        let Some(fs_object) = fs_objects.get(&entry.id) else {
+            continue;
+        };
+        if fs_object.size > limits.max_file_size {
+            tracing::debug!("Content search skips large file {:?}", fs_object.path);
+            continue;
+        }
+
+        let max_matches = remaining.min(MAX_MATCHES_PER_FILE);
+        let matches = match grep_file(&fs_object.path, &matcher, limits.context, max_matches) {
+            Ok(Some(matches)) if !matches.is_empty() => matches,
+            Ok(_) => continue,
+            Err(err) => {
+                tracing::warn!("Content search failed to read {:?}: {err}", fs_object.path);
+                continue;
+            }
+        };
+        remaining -= matches.len();
+
+        let file_matches = FileMatches {
+            path: &entry.path,
+            download: format!("/dl?id={}", entry.id),
+            matches,
+        };
+
+        let mut line = match serde_json::to_string(&file_matches) {
+            Ok(line) => line,
+            Err(err) => {
+                tracing::error!("Failed to serialize content search result: {err}");
+                continue;
+            }
+        };
+        line.push('\n');
+
+        if tx.blocking_send(line).is_err() {
+            tracing::debug!("Content search client disconnected");
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::MatchMode;
+
+    #[test]
+    fn test_grep_reader_context() {
+        let text = "one\ntwo\nERROR 42\nthree\nfour\nerror 43\r\nfive\n";
+        let matcher = Matcher::new("error", MatchMode::Substring).unwrap();
+
+        let matches = grep_reader(text.as_bytes(), &matcher, 1, 10).unwrap();
+        assert_eq!(
+            matches,
+            vec![
+                LineMatch {
+                    line: 3,
+                    text: "ERROR 42".into(),
+                    before: vec!["two".into()],
+                    after: vec!["three".into()],
+                },
+                LineMatch {
+                    line: 6,
+                    text: "error 43".into(),
+                    before: vec!["four".into()],
+                    after: vec!["five".into()],
+                },
+            ]
+        );
+
+        let limited = grep_reader(text.as_bytes(), &matcher, 0, 1).unwrap();
+        assert_eq!(limited.len(), 1);
+        assert!(limited[0].before.is_empty() && limited[0].after.is_empty());
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(!is_binary(b"plain text\n"));
+        assert!(is_binary(b"\x7fELF\x02\x01\x01\x00"));
+    }
+}
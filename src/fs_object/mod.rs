@@ -1,27 +1,64 @@
 //! Module for traversing the filesystem and building an in-memory tree hierarchy.
 
 use std::{
+    cmp::Ordering,
     ffi::OsStr,
     fmt,
     fs::Metadata,
     hash::{DefaultHasher, Hash, Hasher},
     path::PathBuf,
-    sync::Arc,
+    sync::{Arc, OnceLock},
+    time::UNIX_EPOCH,
 };
 
 pub type FsObjects = Vec<Arc<FsObject>>;
 
+/// Unit system used when formatting sizes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SizeUnits {
+    /// Powers of 1024: KiB, MiB, GiB, TiB.
+    #[default]
+    Binary,
+    /// Powers of 1000 (SI): kB, MB, GB, TB.
+    Decimal,
+}
+
+impl SizeUnits {
+    fn base(self) -> f64 {
+        match self {
+            SizeUnits::Binary => 1024.0,
+            SizeUnits::Decimal => 1000.0,
+        }
+    }
+
+    fn suffixes(self) -> &'static [&'static str] {
+        match self {
+            SizeUnits::Binary => &["KiB", "MiB", "GiB", "TiB", "PiB"],
+            SizeUnits::Decimal => &["kB", "MB", "GB", "TB", "PB"],
+        }
+    }
+}
+
 /// Helper struct for formatting file sizes into human-readable strings without heap allocations.
-pub struct SizeFormatter(pub u64);
+pub struct SizeFormatter(pub u64, pub SizeUnits);
 
 impl fmt::Display for SizeFormatter {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let size_kb = self.0 as f64 / 1024.0;
-        if size_kb < 0.1 {
-            write!(f, "{} B", self.0)
-        } else {
-            write!(f, "{:.1} KiB", size_kb)
+        let base = self.1.base();
+        let mut size = self.0 as f64;
+        if size < base {
+            return write!(f, "{} B", self.0);
+        }
+
+        let suffixes = self.1.suffixes();
+        let mut unit = 0;
+        size /= base;
+        while size >= base && unit + 1 < suffixes.len() {
+            size /= base;
+            unit += 1;
         }
+
+        write!(f, "{:.1} {}", size, suffixes[unit])
     }
 }
 
@@ -35,15 +72,37 @@ pub struct FsObject {
 
     /// Directory contents if this entity is a non-empty directory.
     pub content: Option<FsObjects>,
+
+    /// Size in bytes. For directories this is the total size of all nested files.
+    pub size: u64,
+
+    /// Number of all nested elements if this entity is a directory.
+    pub nested_items: usize,
+
+    /// Hex SHA-256 of a file, once computed at scan time or on request.
+    pub sha256: OnceLock<String>,
 }
 
 impl FsObject {
     /// Creates a new [`FsObject`] instance.
+    ///
+    /// Directory size and item count are aggregated from the already scanned `content`.
     pub fn new(path: PathBuf, metadata: Metadata, content: Option<FsObjects>) -> Self {
+        let (size, nested_items) = match content {
+            Some(ref children) => children.iter().fold((0, 0), |(size, items), child| {
+                (size + child.size, items + child.nested_items + 1)
+            }),
+            None if metadata.is_file() => (metadata.len(), 0),
+            None => (0, 0),
+        };
+
         Self {
             path,
             metadata,
             content,
+            size,
+            nested_items,
+            sha256: OnceLock::new(),
         }
     }
 
@@ -66,9 +125,18 @@ impl FsObject {
             .unwrap_or("unnamed")
     }
 
-    /// Returns a displayable formatter for the file size.
-    pub fn size_display(&self) -> SizeFormatter {
-        SizeFormatter(self.metadata.len())
+    /// Returns the last modification time as seconds since the Unix epoch, or `0` if unknown.
+    pub fn modified_secs(&self) -> u64 {
+        self.metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |duration| duration.as_secs())
+    }
+
+    /// Returns a displayable formatter for the file size (or total directory size).
+    pub fn size_display(&self, units: SizeUnits) -> SizeFormatter {
+        SizeFormatter(self.size, units)
     }
 
     /// Returns a depth-first iterator over this node and all nested [`FsObject`] nodes.
@@ -105,6 +173,101 @@ impl Hash for FsObject {
     }
 }
 
+/// Key by which the entries of every directory are ordered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum SortKey {
+    /// Natural order of names, so that "file2" goes before "file10".
+    #[default]
+    Name,
+    /// Largest first.
+    Size,
+    /// Most recently modified first.
+    Mtime,
+}
+
+impl SortKey {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Mtime => "mtime",
+        }
+    }
+}
+
+/// Ordering applied to the [`FsObject`] tree after scanning.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SortOptions {
+    pub key: SortKey,
+    pub dirs_first: bool,
+}
+
+impl SortOptions {
+    pub fn compare(&self, a: &FsObject, b: &FsObject) -> Ordering {
+        if self.dirs_first {
+            let dirs_order = b.is_dir().cmp(&a.is_dir());
+            if dirs_order != Ordering::Equal {
+                return dirs_order;
+            }
+        }
+
+        let by_name = || natural_cmp(a.name(), b.name());
+        match self.key {
+            SortKey::Name => by_name(),
+            SortKey::Size => b.size.cmp(&a.size).then_with(by_name),
+            SortKey::Mtime => b.modified_secs().cmp(&a.modified_secs()).then_with(by_name),
+        }
+    }
+
+    /// Sorts the given nodes in place. Nested directories are expected to be sorted already.
+    pub fn sort(&self, objects: &mut FsObjects) {
+        objects.sort_by(|a, b| self.compare(a, b));
+    }
+}
+
+/// Compares two names case-insensitively, treating runs of digits as numbers.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x_num = take_number(&mut a_chars);
+                let y_num = take_number(&mut b_chars);
+                let x_digits = x_num.trim_start_matches('0');
+                let y_digits = y_num.trim_start_matches('0');
+                let order = x_digits
+                    .len()
+                    .cmp(&y_digits.len())
+                    .then_with(|| x_digits.cmp(y_digits));
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let order = x.to_lowercase().cmp(y.to_lowercase());
+                if order != Ordering::Equal {
+                    return order;
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> String {
+    let mut number = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        number.push(c);
+    }
+    number
+}
+
 /// Holds aggregated counters for a collection of filesystem elements.
 #[derive(Debug, Default, PartialEq, Eq)]
 pub struct FsSummary {
@@ -144,3 +307,51 @@ impl fmt::Display for FsSummary {
         writeln!(f, "\t\t{} symbolic links\n", self.total_symlinks)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_formatter() {
+        let binary = |bytes| SizeFormatter(bytes, SizeUnits::Binary).to_string();
+        let decimal = |bytes| SizeFormatter(bytes, SizeUnits::Decimal).to_string();
+
+        assert_eq!(binary(0), "0 B");
+        assert_eq!(binary(1023), "1023 B");
+        assert_eq!(binary(1024), "1.0 KiB");
+        assert_eq!(binary(1536), "1.5 KiB");
+        assert_eq!(binary(4 * 1024 * 1024 * 1024), "4.0 GiB");
+        assert_eq!(binary(3 * 1024_u64.pow(4)), "3.0 TiB");
+        assert_eq!(binary(5 * 1024_u64.pow(6)), "5120.0 PiB");
+
+        assert_eq!(decimal(999), "999 B");
+        assert_eq!(decimal(1000), "1.0 kB");
+        assert_eq!(decimal(2_500_000), "2.5 MB");
+        assert_eq!(decimal(4_000_000_000), "4.0 GB");
+    }
+
+    #[test]
+    fn test_natural_cmp() {
+        let mut names = vec![
+            "file10.txt",
+            "File2.txt",
+            "file1.txt",
+            "a",
+            "file02.txt",
+            "B",
+        ];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            [
+                "a",
+                "B",
+                "file1.txt",
+                "File2.txt",
+                "file02.txt",
+                "file10.txt"
+            ]
+        );
+    }
+}
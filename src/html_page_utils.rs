@@ -1,12 +1,48 @@
-use crate::fs_object::{FsObject, FsObjects};
+use crate::archive::ArchiveFormat;
+use crate::fs_object::{FsObject, FsObjects, SizeUnits};
+use crate::preview::{document, markdown};
+use crate::thumbnail::is_image;
 use std::collections::HashMap;
-use std::fmt::Write;
+use std::fmt::{self, Write};
 use std::sync::Arc;
 
-pub fn unordered_list(files: &FsObjects, hash_map: &mut HashMap<u64, Arc<FsObject>>) -> Box<str> {
+/// Escapes text for use inside HTML content and attribute values without heap allocations.
+pub struct HtmlEscaped<'a>(pub &'a str);
+
+impl fmt::Display for HtmlEscaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rest = self.0;
+        while let Some(pos) = rest.find(['&', '<', '>', '"', '\'']) {
+            f.write_str(&rest[..pos])?;
+            f.write_str(match rest.as_bytes()[pos] {
+                b'&' => "&amp;",
+                b'<' => "&lt;",
+                b'>' => "&gt;",
+                b'"' => "&quot;",
+                _ => "&#39;",
+            })?;
+            rest = &rest[pos + 1..];
+        }
+        f.write_str(rest)
+    }
+}
+
+/// Settings affecting how the listing is rendered.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ListOptions {
+    pub units: SizeUnits,
+    /// Show first-page thumbnails next to PDF files.
+    pub pdf_thumbnails: bool,
+}
+
+pub fn unordered_list(
+    files: &FsObjects,
+    hash_map: &mut HashMap<u64, Arc<FsObject>>,
+    options: ListOptions,
+) -> Box<str> {
     let mut html_buf = String::with_capacity(files.len() * 100 + 32);
 
-    render_unordered_list(files, hash_map, &mut html_buf);
+    render_unordered_list(files, hash_map, options, &mut html_buf);
 
     html_buf.into_boxed_str()
 }
@@ -14,43 +50,147 @@ pub fn unordered_list(files: &FsObjects, hash_map: &mut HashMap<u64, Arc<FsObjec
 fn render_unordered_list(
     files: &FsObjects,
     hash_map: &mut HashMap<u64, Arc<FsObject>>,
+    options: ListOptions,
     buf: &mut String,
 ) {
-    buf.push_str("<ul>\n");
-    render_list_items(files, hash_map, buf);
+    let gallery = is_gallery(files);
+    buf.push_str(if gallery {
+        "<ul class=\"gallery\">\n"
+    } else {
+        "<ul>\n"
+    });
+    render_list_items(files, hash_map, options, gallery, buf);
     buf.push_str("</ul>\n");
 }
 
 fn render_list_items(
     items: &FsObjects,
     hash_map: &mut HashMap<u64, Arc<FsObject>>,
+    options: ListOptions,
+    gallery: bool,
     buf: &mut String,
 ) {
+    let units = options.units;
     for item in items {
+        let name = HtmlEscaped(item.name());
+        let tile = gallery && item.is_file() && is_image(&item.path);
+        let _ = write!(
+            buf,
+            r#"<li data-name="{name}" data-size="{}" data-mtime="{}"{}>"#,
+            item.size,
+            item.modified_secs(),
+            if item.is_dir() {
+                " data-dir"
+            } else if tile {
+                r#" class="gallery-item""#
+            } else {
+                ""
+            }
+        );
+
         if item.is_dir() {
-            buf.push_str("<li><details><summary>📁 ");
-            buf.push_str(item.name());
-            buf.push_str("</summary>\n");
+            let _ = writeln!(
+                buf,
+                r#"<details><summary>📁 {name} <span class="dir-meta">({} {}, {})</span> <a href="/sum?id={}" class="checksum-link">[SHA256SUMS]</a></summary>"#,
+                item.nested_items,
+                if item.nested_items == 1 {
+                    "item"
+                } else {
+                    "items"
+                },
+                item.size_display(units),
+                item.get_hash()
+            );
 
             if let Some(content) = &item.content {
-                render_unordered_list(content, hash_map, buf);
+                render_unordered_list(content, hash_map, options, buf);
+                render_readme(content, buf);
             }
 
             buf.push_str("</details></li>\n");
         } else if item.is_symlink() {
-            buf.push_str("<li>🔗 ");
-            buf.push_str(item.name());
+            let _ = writeln!(buf, "🔗 {name}</li>");
+        } else if ArchiveFormat::detect(&item.path).is_some() {
+            let hash = item.get_hash();
+            hash_map.insert(hash, Arc::clone(item));
+
+            let _ = write!(
+                buf,
+                r#"<details class="archive" data-archive-id="{hash}"><summary>🗜 <a href="/dl?id={hash}">{name}</a>, {} <a href="/pw?id={hash}&amp;mode=hex">[hex]</a> "#,
+                item.size_display(units)
+            );
+            render_checksum(item, hash, buf);
+            buf.push_str("</summary><ul class=\"archive-members\"></ul></details></li>\n");
+        } else if tile {
+            let hash = item.get_hash();
+            hash_map.insert(hash, Arc::clone(item));
+
+            let _ = write!(
+                buf,
+                r#"<a href="/pw?id={hash}" class="lightbox-link" title="{name}"><img src="/thumb?id={hash}" alt="{name}" loading="lazy"></a><a href="/dl?id={hash}" class="gallery-caption">{name}</a>"#
+            );
+            render_checksum(item, hash, buf);
             buf.push_str("</li>\n");
         } else {
             let hash = item.get_hash();
             hash_map.insert(hash, Arc::clone(item));
 
-            let _ = writeln!(
+            if options.pdf_thumbnails && document::is_pdf(&item.path) {
+                let _ = write!(
+                    buf,
+                    r#"<img class="doc-thumb" src="/thumb?id={hash}" alt="" loading="lazy" onerror="this.remove()">"#
+                );
+            }
+
+            let _ = write!(
                 buf,
-                r#"<li>🗋 <a href="/dl?id={hash}">{}</a>, {} <a href="/pw?id={hash}">[view]</a></li>"#,
-                item.name(),
-                item.size_display()
+                r#"🗋 <a href="/dl?id={hash}">{name}</a>, {} <a href="/pw?id={hash}">[view]</a> <a href="/pw?id={hash}&amp;mode=hex">[hex]</a> "#,
+                item.size_display(units)
             );
+            render_checksum(item, hash, buf);
+            buf.push_str("</li>\n");
         }
     }
 }
+
+/// Renders the file's SHA-256 if it was precomputed, otherwise a link to compute it.
+fn render_checksum(item: &FsObject, hash: u64, buf: &mut String) {
+    let _ = match item.sha256.get() {
+        Some(sha256) => write!(
+            buf,
+            r#"<code class="checksum" title="SHA-256">{sha256}</code>"#
+        ),
+        None => write!(
+            buf,
+            r#"<a href="/sum?id={hash}" class="checksum-link">[sha256]</a>"#
+        ),
+    };
+}
+
+/// Renders the directory's `README.md` (if any) below its listing.
+fn render_readme(items: &FsObjects, buf: &mut String) {
+    let readme = items
+        .iter()
+        .find(|item| item.is_file() && markdown::is_readme(&item.path));
+
+    if let Some(html) = readme.and_then(|readme| markdown::render_file(&readme.path, readme.size)) {
+        buf.push_str("<div class=\"readme markdown\">\n");
+        buf.push_str(&html);
+        buf.push_str("</div>\n");
+    }
+}
+
+/// Minimum number of images in a directory for it to be rendered as a gallery.
+const GALLERY_MIN_IMAGES: usize = 4;
+
+/// Checks whether images make up at least half of the files in the list.
+fn is_gallery(items: &FsObjects) -> bool {
+    let (files, images) = items
+        .iter()
+        .filter(|item| item.is_file())
+        .fold((0, 0), |(files, images), item| {
+            (files + 1, images + usize::from(is_image(&item.path)))
+        });
+
+    images >= GALLERY_MIN_IMAGES && images * 2 >= files
+}
@@ -1,14 +1,21 @@
+mod archive;
+mod checksum;
 mod cli_args;
+mod content_search;
 mod fs_object;
 mod html_page_utils;
+mod preview;
+mod search;
 mod server_receiver_mode;
 mod server_transmitter_mode;
 mod storage;
 mod style;
+#[cfg(test)]
+mod test_util;
+mod thumbnail;
 use clap::Parser;
 use qrcode::{render::unicode, QrCode};
 use std::net::SocketAddr;
-use tracing_subscriber;
 use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
@@ -22,10 +29,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let mut cli_args = cli_args::Args::parse();
 
-    let app;
-    match cli_args.receive {
-        true => app = server_receiver_mode::setup(&cli_args),
-        false => app = server_transmitter_mode::setup(&mut cli_args)?,
+    let app = match cli_args.receive {
+        true => server_receiver_mode::setup(&cli_args),
+        false => server_transmitter_mode::setup(&mut cli_args)?,
     };
 
     let local_ip = local_ip_address::local_ip()?;
@@ -38,7 +44,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!("Listening {url}");
     tracing::info!("{}", qrcode);
 
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
@@ -0,0 +1,413 @@
+//! Structured-data previews: CSV/TSV files as a paginated sortable table and
+//! JSON/NDJSON files as a collapsible tree.
+
+use super::extension;
+use crate::fs_object::natural_cmp;
+use crate::html_page_utils::HtmlEscaped;
+use askama::Template;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+/// Rows beyond this limit are not loaded into the table.
+pub const MAX_TABLE_ROWS: usize = 10_000;
+
+/// Only the leading part of a larger table file is read.
+pub const MAX_TABLE_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Rows shown on a single table page.
+pub const TABLE_PAGE_SIZE: usize = 100;
+
+/// Larger JSON documents are previewed as plain text.
+pub const MAX_JSON_SIZE: u64 = 4 * 1024 * 1024;
+
+/// NDJSON records beyond this limit are not rendered.
+pub const MAX_JSON_RECORDS: usize = 1000;
+
+/// Nesting levels of the JSON tree that are expanded initially.
+const JSON_OPEN_DEPTH: usize = 2;
+
+const TABLE_EXTENSIONS: &[&str] = &["csv", "tsv", "tab"];
+const JSON_EXTENSIONS: &[&str] = &["json", "geojson", "ndjson", "jsonl"];
+const NDJSON_EXTENSIONS: &[&str] = &["ndjson", "jsonl"];
+
+pub fn is_table(path: &Path) -> bool {
+    extension(path).is_some_and(|ext| TABLE_EXTENSIONS.contains(&ext.as_str()))
+}
+
+pub fn is_json(path: &Path) -> bool {
+    extension(path).is_some_and(|ext| JSON_EXTENSIONS.contains(&ext.as_str()))
+}
+
+fn is_ndjson(path: &Path) -> bool {
+    extension(path).is_some_and(|ext| NDJSON_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Rows of a CSV/TSV file, all padded to the same number of columns.
+pub struct Table {
+    pub header: Option<Vec<String>>,
+    pub rows: Vec<Vec<String>>,
+    pub columns: usize,
+    /// Whether rows were left out because of [`MAX_TABLE_ROWS`] or [`MAX_TABLE_BYTES`].
+    pub truncated: bool,
+}
+
+impl Table {
+    /// Reads the table. The first row is treated as a header if `header` says so,
+    /// or, when it is `None`, if it looks like one.
+    pub fn read(path: &Path, header: Option<bool>) -> io::Result<Self> {
+        let delimiter = match extension(path).as_deref() {
+            Some("tsv" | "tab") => b'\t',
+            _ => sniff_delimiter(path)?,
+        };
+
+        let file = File::open(path)?;
+        let truncated_by_size = file.metadata()?.len() > MAX_TABLE_BYTES;
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(file.take(MAX_TABLE_BYTES));
+
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        let mut truncated = truncated_by_size;
+        for record in reader.records() {
+            let record = match record {
+                Ok(record) => record,
+                // The last record may be cut by the size limit
+                Err(_) if truncated_by_size => break,
+                Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+            };
+            if rows.len() > MAX_TABLE_ROWS {
+                truncated = true;
+                break;
+            }
+            rows.push(record.iter().map(str::to_owned).collect());
+        }
+
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        for row in &mut rows {
+            row.resize(columns, String::new());
+        }
+
+        let header = if header.unwrap_or_else(|| looks_like_header(&rows)) && !rows.is_empty() {
+            Some(rows.remove(0))
+        } else {
+            None
+        };
+        if rows.len() > MAX_TABLE_ROWS {
+            rows.truncate(MAX_TABLE_ROWS);
+            truncated = true;
+        }
+
+        Ok(Self {
+            header,
+            rows,
+            columns,
+            truncated,
+        })
+    }
+
+    /// Stable sort by the column, numerically if both cells are numbers.
+    pub fn sort(&mut self, column: usize, descending: bool) {
+        if column >= self.columns {
+            return;
+        }
+        self.rows.sort_by(|a, b| {
+            let ordering = compare_cells(&a[column], &b[column]);
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+}
+
+/// Picks the most frequent of `,`, `;` and tab in the first line.
+fn sniff_delimiter(path: &Path) -> io::Result<u8> {
+    let mut first_line = Vec::new();
+    BufReader::new(File::open(path)?)
+        .take(64 * 1024)
+        .read_until(b'\n', &mut first_line)?;
+
+    let delimiter = [b',', b';', b'\t']
+        .into_iter()
+        .max_by_key(|&delimiter| {
+            (
+                first_line.iter().filter(|&&byte| byte == delimiter).count(),
+                // Prefer the comma on ties
+                delimiter == b',',
+            )
+        })
+        .unwrap_or(b',');
+    Ok(delimiter)
+}
+
+fn is_number(cell: &str) -> bool {
+    cell.trim().parse::<f64>().is_ok()
+}
+
+/// The first row is a header if its cells are non-empty, unique and not numbers.
+fn looks_like_header(rows: &[Vec<String>]) -> bool {
+    let Some(first) = rows.first() else {
+        return false;
+    };
+    if rows.len() < 2 {
+        return false;
+    }
+
+    first
+        .iter()
+        .enumerate()
+        .all(|(i, cell)| !cell.trim().is_empty() && !is_number(cell) && !first[..i].contains(cell))
+}
+
+fn compare_cells(a: &str, b: &str) -> Ordering {
+    match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.total_cmp(&b),
+        // Numbers go before text, empty cells go last
+        (Ok(_), Err(_)) if !b.is_empty() => Ordering::Less,
+        (Err(_), Ok(_)) if !a.is_empty() => Ordering::Greater,
+        _ => match (a.is_empty(), b.is_empty()) {
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            _ => natural_cmp(a, b),
+        },
+    }
+}
+
+/// A column heading with the link that sorts the table by it.
+pub struct TableColumn {
+    pub name: String,
+    pub href: String,
+    /// Arrow shown next to the column the table is sorted by.
+    pub indicator: &'static str,
+}
+
+#[derive(Template)]
+#[template(path = "preview/table.html")]
+pub struct TableTemplate<'a> {
+    pub title: &'a str,
+    pub name: &'a str,
+    pub id: u64,
+    pub size: String,
+    pub columns: Vec<TableColumn>,
+    pub rows: &'a [Vec<String>],
+    pub has_header: bool,
+    /// Link toggling header detection.
+    pub header_href: String,
+    pub first_row: usize,
+    pub total_rows: usize,
+    pub page: usize,
+    pub pages: usize,
+    pub prev_href: Option<String>,
+    pub next_href: Option<String>,
+    pub truncated: bool,
+}
+
+/// Rendered JSON or NDJSON document.
+pub struct JsonPreview {
+    pub tree: String,
+    pub pretty: String,
+    /// Number of rendered records for NDJSON files.
+    pub records: Option<usize>,
+    pub truncated: bool,
+}
+
+impl JsonPreview {
+    /// Reads and renders a JSON file, `None` if it is too large or not valid JSON.
+    pub fn read(path: &Path, size: u64) -> Option<Self> {
+        let result = if is_ndjson(path) {
+            Self::read_ndjson(path)
+        } else if size > MAX_JSON_SIZE {
+            return None;
+        } else {
+            Self::read_json(path)
+        };
+
+        match result {
+            Ok(preview) => Some(preview),
+            Err(err) => {
+                tracing::warn!("Failed to read JSON file {}: {err}", path.display());
+                None
+            }
+        }
+    }
+
+    fn read_json(path: &Path) -> io::Result<Self> {
+        let value: Value = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+
+        let mut tree = String::new();
+        render_tree(&value, 0, &mut tree);
+
+        Ok(Self {
+            tree,
+            pretty: serde_json::to_string_pretty(&value)?,
+            records: None,
+            truncated: false,
+        })
+    }
+
+    fn read_ndjson(path: &Path) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?.take(MAX_JSON_SIZE));
+
+        let mut tree = String::from("<ol class=\"json-records\">\n");
+        let mut pretty = String::new();
+        let mut records = 0;
+        let mut truncated = false;
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if records == MAX_JSON_RECORDS {
+                truncated = true;
+                break;
+            }
+
+            let value: Value = match serde_json::from_str(&line) {
+                Ok(value) => value,
+                // The last line may be cut by the size limit
+                Err(err) if err.is_eof() && records > 0 => {
+                    truncated = true;
+                    break;
+                }
+                Err(err) => return Err(err.into()),
+            };
+            records += 1;
+
+            tree.push_str("<li>");
+            render_tree(&value, 1, &mut tree);
+            tree.push_str("</li>\n");
+            pretty.push_str(&serde_json::to_string_pretty(&value)?);
+            pretty.push('\n');
+        }
+        tree.push_str("</ol>\n");
+
+        Ok(Self {
+            tree,
+            pretty,
+            records: Some(records),
+            truncated,
+        })
+    }
+}
+
+/// Renders the value as nested `<details>` elements, keys and scalars escaped.
+fn render_tree(value: &Value, depth: usize, buf: &mut String) {
+    let open = if depth < JSON_OPEN_DEPTH { " open" } else { "" };
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            let _ = write!(
+                buf,
+                "<details{open}><summary>{{…}} <span class=\"json-count\">{} {}</span></summary><ul>",
+                map.len(),
+                if map.len() == 1 { "key" } else { "keys" }
+            );
+            for (key, value) in map {
+                let _ = write!(
+                    buf,
+                    "<li><span class=\"json-key\">\"{}\"</span>: ",
+                    HtmlEscaped(key)
+                );
+                render_tree(value, depth + 1, buf);
+                buf.push_str("</li>");
+            }
+            buf.push_str("</ul></details>");
+        }
+        Value::Array(items) if !items.is_empty() => {
+            let _ = write!(
+                buf,
+                "<details{open}><summary>[…] <span class=\"json-count\">{} {}</span></summary><ol start=\"0\">",
+                items.len(),
+                if items.len() == 1 { "item" } else { "items" }
+            );
+            for item in items {
+                buf.push_str("<li>");
+                render_tree(item, depth + 1, buf);
+                buf.push_str("</li>");
+            }
+            buf.push_str("</ol></details>");
+        }
+        Value::Object(_) => buf.push_str("<span class=\"json-punct\">{}</span>"),
+        Value::Array(_) => buf.push_str("<span class=\"json-punct\">[]</span>"),
+        Value::String(text) => {
+            let _ = write!(
+                buf,
+                "<span class=\"json-string\">\"{}\"</span>",
+                HtmlEscaped(text)
+            );
+        }
+        Value::Number(number) => {
+            let _ = write!(buf, "<span class=\"json-number\">{number}</span>");
+        }
+        Value::Bool(_) | Value::Null => {
+            let _ = write!(buf, "<span class=\"json-literal\">{value}</span>");
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "preview/json.html")]
+pub struct JsonTemplate<'a> {
+    pub title: &'a str,
+    pub name: &'a str,
+    pub id: u64,
+    pub size: String,
+    pub json: JsonPreview,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn rows(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter()
+            .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_looks_like_header() {
+        assert!(looks_like_header(&rows(&[&["name", "size"], &["a", "1"]])));
+        assert!(!looks_like_header(&rows(&[&["1", "2"], &["3", "4"]])));
+        assert!(!looks_like_header(&rows(&[&["a", "a"], &["b", "c"]])));
+        assert!(!looks_like_header(&rows(&[&["name", ""], &["b", "c"]])));
+        assert!(!looks_like_header(&rows(&[&["name", "size"]])));
+    }
+
+    #[test]
+    fn test_read_and_sort_table() {
+        let dir = TempDir::new();
+        let path = dir.join("table.csv");
+        std::fs::write(&path, "name;size\nb;10\na;9\nc\n").unwrap();
+
+        let mut table = Table::read(&path, None).unwrap();
+        assert_eq!(table.header, Some(vec!["name".into(), "size".into()]));
+        assert_eq!(table.columns, 2);
+        assert_eq!(table.rows[2], vec!["c".to_string(), String::new()]);
+
+        table.sort(1, false);
+        assert_eq!(table.rows[0][0], "a");
+        assert_eq!(table.rows[2][0], "c");
+        table.sort(0, true);
+        assert_eq!(table.rows[0][0], "c");
+    }
+
+    #[test]
+    fn test_render_tree_escapes() {
+        let value: Value = serde_json::from_str(r#"{"<k>": ["<b>", 1, null, {}]}"#).unwrap();
+        let mut html = String::new();
+        render_tree(&value, 0, &mut html);
+        assert!(html.contains("&lt;k&gt;"));
+        assert!(html.contains("\"&lt;b&gt;\""));
+        assert!(html.contains("<span class=\"json-number\">1</span>"));
+        assert!(html.contains("<span class=\"json-literal\">null</span>"));
+        assert!(!html.contains("<b>"));
+    }
+}
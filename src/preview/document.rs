@@ -0,0 +1,197 @@
+//! PDF and office document previews.
+//!
+//! PDFs are shown by the browser's built-in viewer. First-page thumbnails are rendered with
+//! `pdftoppm` (poppler-utils) and office documents are converted to PDF with LibreOffice,
+//! when these tools are installed.
+
+use super::extension;
+use crate::checksum::to_hex;
+use askama::Template;
+use std::{
+    fs::DirBuilder,
+    io,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::{Duration, UNIX_EPOCH},
+};
+use tokio::{process::Command, sync::Mutex};
+
+const OFFICE_EXTENSIONS: &[&str] = &[
+    "doc", "docx", "odt", "rtf", "xls", "xlsx", "ods", "ppt", "pptx", "odp",
+];
+
+/// Resolution of the rendered first page, the thumbnail is downscaled from it.
+const PDF_RASTER_SIZE: u32 = 512;
+
+const PDF_RASTER_TIMEOUT: Duration = Duration::from_secs(30);
+const OFFICE_CONVERT_TIMEOUT: Duration = Duration::from_secs(120);
+
+pub fn is_pdf(path: &Path) -> bool {
+    extension(path).is_some_and(|ext| ext == "pdf")
+}
+
+pub fn is_office(path: &Path) -> bool {
+    extension(path).is_some_and(|ext| OFFICE_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// External tools used for document previews and their availability.
+pub struct DocumentTools {
+    pub pdftoppm: bool,
+    pub soffice: bool,
+    /// LibreOffice can't run several conversions with the same profile at once.
+    convert_lock: Mutex<()>,
+    /// Private directory of the converted documents, unique to this process.
+    cache_dir: PathBuf,
+}
+
+impl DocumentTools {
+    /// Checks which tools are installed.
+    pub fn detect() -> Self {
+        let available = |program: &str, arg: &str| {
+            std::process::Command::new(program)
+                .arg(arg)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok()
+        };
+
+        let mut tools = Self {
+            pdftoppm: available("pdftoppm", "-v"),
+            soffice: available("soffice", "--version"),
+            convert_lock: Mutex::new(()),
+            cache_dir: PathBuf::new(),
+        };
+
+        if tools.soffice {
+            match create_cache_dir() {
+                Ok(cache_dir) => tools.cache_dir = cache_dir,
+                Err(err) => {
+                    tracing::warn!("Failed to create the converted documents directory: {err}");
+                    tools.soffice = false;
+                }
+            }
+        }
+
+        if !tools.pdftoppm {
+            tracing::info!("pdftoppm not found, PDF thumbnails are disabled");
+        }
+        if !tools.soffice {
+            tracing::info!("soffice not found, office document previews are disabled");
+        }
+
+        tools
+    }
+
+    /// Renders the first page of a PDF to PNG.
+    pub async fn first_page_png(&self, pdf: &Path) -> io::Result<Vec<u8>> {
+        if !self.pdftoppm {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "pdftoppm is not available",
+            ));
+        }
+
+        let output = Command::new("pdftoppm")
+            .args(["-f", "1", "-l", "1", "-singlefile", "-png", "-scale-to"])
+            .arg(PDF_RASTER_SIZE.to_string())
+            .arg(pdf)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .output();
+
+        let output = tokio::time::timeout(PDF_RASTER_TIMEOUT, output)
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "pdftoppm timed out"))??;
+
+        if !output.status.success() || output.stdout.is_empty() {
+            return Err(io::Error::other(format!(
+                "pdftoppm failed with {}",
+                output.status
+            )));
+        }
+
+        Ok(output.stdout)
+    }
+
+    /// Converts an office document to PDF, reusing earlier conversions of the same file
+    /// while its size and modification time stay the same.
+    pub async fn office_to_pdf(&self, id: u64, document: &Path) -> io::Result<PathBuf> {
+        if !self.soffice {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "soffice is not available",
+            ));
+        }
+
+        let metadata = tokio::fs::metadata(document).await?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let target_dir =
+            self.cache_dir
+                .join(format!("{id}-{}-{}", modified.as_nanos(), metadata.len()));
+        // soffice keeps every dot of the stem, e.g. `report.v2.docx` becomes `report.v2.pdf`
+        let mut pdf_name = document
+            .file_stem()
+            .unwrap_or(document.as_os_str())
+            .to_os_string();
+        pdf_name.push(".pdf");
+        let target = target_dir.join(pdf_name);
+
+        let _guard = self.convert_lock.lock().await;
+        if tokio::fs::try_exists(&target).await? {
+            return Ok(target);
+        }
+
+        tokio::fs::create_dir_all(&target_dir).await?;
+        tracing::info!("Converting {} to PDF", document.display());
+
+        let status = Command::new("soffice")
+            .args(["--headless", "--convert-to", "pdf", "--outdir"])
+            .arg(&target_dir)
+            .arg(document)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .status();
+
+        let status = tokio::time::timeout(OFFICE_CONVERT_TIMEOUT, status)
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "soffice timed out"))??;
+
+        if !status.success() || !tokio::fs::try_exists(&target).await? {
+            return Err(io::Error::other(format!("soffice failed with {status}")));
+        }
+
+        Ok(target)
+    }
+}
+
+/// Creates a directory with a random name in the temp directory, readable only by the
+/// current user on Unix.
+fn create_cache_dir() -> io::Result<PathBuf> {
+    let mut bytes = [0; 8];
+    getrandom::fill(&mut bytes).map_err(|err| io::Error::other(err.to_string()))?;
+    let path = std::env::temp_dir().join(format!("minicloud-documents-{}", to_hex(&bytes)));
+
+    let mut builder = DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(&path)?;
+    Ok(path)
+}
+
+#[derive(Template)]
+#[template(path = "preview/document.html")]
+pub struct DocumentTemplate<'a> {
+    pub title: &'a str,
+    pub name: &'a str,
+    pub id: u64,
+    pub size: String,
+    /// URL of the PDF shown in the viewer, `None` if the document can't be displayed.
+    pub pdf_url: Option<String>,
+}
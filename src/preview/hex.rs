@@ -0,0 +1,131 @@
+//! Paged hex dump of arbitrary files. Only the requested page is read from disk,
+//! so multi-gigabyte images can be browsed.
+
+use crate::html_page_utils::HtmlEscaped;
+use askama::Template;
+use serde::Serialize;
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// Bytes per dump line.
+pub const HEX_LINE_SIZE: u64 = 16;
+
+/// Bytes rendered per page.
+pub const HEX_PAGE_SIZE: u64 = 4096;
+
+/// One page of the dump.
+#[derive(Serialize)]
+pub struct HexPage {
+    pub html: String,
+    pub offset: u64,
+    pub prev_offset: Option<u64>,
+    pub next_offset: Option<u64>,
+}
+
+/// Reads and renders the page starting at `offset`, rounded down to a line start.
+pub fn render_page(path: &Path, file_size: u64, offset: u64) -> io::Result<HexPage> {
+    let offset = offset.min(file_size.saturating_sub(1)) / HEX_LINE_SIZE * HEX_LINE_SIZE;
+
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut bytes = Vec::with_capacity(HEX_PAGE_SIZE as usize);
+    file.take(HEX_PAGE_SIZE).read_to_end(&mut bytes)?;
+
+    let end = offset + bytes.len() as u64;
+
+    Ok(HexPage {
+        html: dump(&bytes, offset),
+        offset,
+        prev_offset: (offset > 0).then(|| offset.saturating_sub(HEX_PAGE_SIZE)),
+        next_offset: (end < file_size).then_some(end),
+    })
+}
+
+/// Formats the bytes as `offset  hex bytes  |ascii|` lines.
+fn dump(bytes: &[u8], offset: u64) -> String {
+    let mut buf = String::with_capacity(bytes.len() / HEX_LINE_SIZE as usize * 100 + 100);
+
+    for (i, line) in bytes.chunks(HEX_LINE_SIZE as usize).enumerate() {
+        let line_offset = offset + i as u64 * HEX_LINE_SIZE;
+        let _ = write!(
+            buf,
+            r#"<span class="hex-line" id="x{line_offset:x}"><span class="hex-offset">{line_offset:08x}</span> "#
+        );
+
+        for column in 0..HEX_LINE_SIZE as usize {
+            if column % 8 == 0 {
+                buf.push(' ');
+            }
+            match line.get(column) {
+                Some(byte) => {
+                    let _ = write!(buf, "{byte:02x} ");
+                }
+                None => buf.push_str("   "),
+            }
+        }
+
+        let ascii: String = line
+            .iter()
+            .map(|&byte| match byte {
+                0x20..=0x7e => byte as char,
+                _ => '.',
+            })
+            .collect();
+        let _ = writeln!(
+            buf,
+            r#" <span class="hex-ascii">|{}|</span></span>"#,
+            HtmlEscaped(&ascii)
+        );
+    }
+
+    buf
+}
+
+#[derive(Template)]
+#[template(path = "preview/hex.html")]
+pub struct HexTemplate<'a> {
+    pub title: &'a str,
+    pub name: &'a str,
+    pub id: u64,
+    pub size: String,
+    pub file_size: u64,
+    pub page: HexPage,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_dump() {
+        let html = dump(b"ABC<\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0bZ", 32);
+        let mut lines = html.lines();
+        let first = lines.next().unwrap();
+        assert!(first.contains(">00000020</span>  41 42 43 3c 00 01 02 03  04 05"));
+        assert!(first.contains("|ABC&lt;............|"));
+        let second = lines.next().unwrap();
+        assert!(second.contains(r#"id="x30""#));
+        assert!(second.contains("|Z|"));
+    }
+
+    #[test]
+    fn test_render_page_offsets() {
+        let dir = TempDir::new();
+        let path = dir.join("page.bin");
+        std::fs::write(&path, vec![0u8; 10_000]).unwrap();
+
+        let page = render_page(&path, 10_000, 5000).unwrap();
+        assert_eq!(page.offset, 4992);
+        assert_eq!(page.prev_offset, Some(896));
+        assert_eq!(page.next_offset, Some(9088));
+
+        let last = render_page(&path, 10_000, 20_000).unwrap();
+        assert_eq!(last.offset, 9984);
+        assert_eq!(last.next_offset, None);
+    }
+}
@@ -0,0 +1,86 @@
+//! Markdown rendering for `.md` previews and README files shown in the listing.
+
+use super::extension;
+use askama::Template;
+use pulldown_cmark::{html, Options, Parser};
+use std::path::Path;
+
+/// Larger Markdown files are previewed as plain text.
+pub const MAX_MARKDOWN_SIZE: u64 = 1024 * 1024;
+
+const MARKDOWN_EXTENSIONS: &[&str] = &["md", "markdown"];
+
+pub fn is_markdown(path: &Path) -> bool {
+    extension(path).is_some_and(|ext| MARKDOWN_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Checks whether the file name is `README.md` or a similar variant.
+pub fn is_readme(path: &Path) -> bool {
+    is_markdown(path)
+        && path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .is_some_and(|stem| stem.eq_ignore_ascii_case("readme"))
+}
+
+/// Converts Markdown to HTML and strips everything that could run script in the page.
+pub fn render(markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES;
+
+    let mut unsafe_html = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut unsafe_html, Parser::new_ext(markdown, options));
+
+    ammonia::clean(&unsafe_html)
+}
+
+/// Reads and renders a Markdown file, `None` if it is too large or unreadable.
+pub fn render_file(path: &Path, size: u64) -> Option<String> {
+    if size > MAX_MARKDOWN_SIZE {
+        return None;
+    }
+
+    match std::fs::read(path) {
+        Ok(content) => Some(render(&String::from_utf8_lossy(&content))),
+        Err(err) => {
+            tracing::warn!("Failed to read Markdown file {}: {err}", path.display());
+            None
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "preview/markdown.html")]
+pub struct MarkdownTemplate<'a> {
+    pub title: &'a str,
+    pub name: &'a str,
+    pub id: u64,
+    pub size: String,
+    pub html: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_sanitizes() {
+        let html = render(
+            "# Title\n\n<script>alert(1)</script>\n\n[link](javascript:alert(1)) <img src=x onerror=alert(1)>",
+        );
+        assert!(html.contains("<h1>Title</h1>"));
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("javascript:"));
+        assert!(!html.contains("onerror"));
+    }
+
+    #[test]
+    fn test_is_readme() {
+        assert!(is_readme(Path::new("/share/README.md")));
+        assert!(is_readme(Path::new("readme.markdown")));
+        assert!(!is_readme(Path::new("notes.md")));
+        assert!(!is_readme(Path::new("README.txt")));
+    }
+}
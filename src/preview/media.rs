@@ -0,0 +1,135 @@
+//! HTML5 player page for video and audio files.
+
+use super::extension;
+use crate::fs_object::FsObject;
+use askama::Template;
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MediaKind {
+    Video,
+    Audio,
+}
+
+/// Extensions of the media files and their MIME types. The page offers a download
+/// instead when the browser can't play the type.
+const MEDIA_TYPES: &[(&str, MediaKind, &str)] = &[
+    ("mp4", MediaKind::Video, "video/mp4"),
+    ("m4v", MediaKind::Video, "video/mp4"),
+    ("mov", MediaKind::Video, "video/quicktime"),
+    ("webm", MediaKind::Video, "video/webm"),
+    ("mkv", MediaKind::Video, "video/x-matroska"),
+    ("ogv", MediaKind::Video, "video/ogg"),
+    ("mp3", MediaKind::Audio, "audio/mpeg"),
+    ("m4a", MediaKind::Audio, "audio/mp4"),
+    ("aac", MediaKind::Audio, "audio/aac"),
+    ("flac", MediaKind::Audio, "audio/flac"),
+    ("wav", MediaKind::Audio, "audio/wav"),
+    ("ogg", MediaKind::Audio, "audio/ogg"),
+    ("oga", MediaKind::Audio, "audio/ogg"),
+    ("opus", MediaKind::Audio, "audio/ogg"),
+    ("weba", MediaKind::Audio, "audio/webm"),
+];
+
+const SUBTITLE_EXTENSIONS: &[&str] = &["vtt", "srt"];
+
+pub fn media_type(path: &Path) -> Option<(MediaKind, &'static str)> {
+    let ext = extension(path)?;
+    MEDIA_TYPES
+        .iter()
+        .find(|(known, _, _)| *known == ext)
+        .map(|&(_, kind, mime)| (kind, mime))
+}
+
+pub fn is_subtitle(path: &Path) -> bool {
+    extension(path).is_some_and(|ext| SUBTITLE_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Subtitle file found next to a media file.
+pub struct Subtitle {
+    pub id: u64,
+    /// Language tag from names like `movie.en.srt`, empty if absent.
+    pub lang: String,
+    pub label: String,
+}
+
+/// Finds `<stem>.vtt`, `<stem>.srt` and `<stem>.<lang>.srt` siblings of the media file.
+pub fn find_subtitles(media: &FsObject, fs_objects: &HashMap<u64, Arc<FsObject>>) -> Vec<Subtitle> {
+    let (Some(parent), Some(stem)) = (
+        media.path.parent(),
+        media.path.file_stem().and_then(|stem| stem.to_str()),
+    ) else {
+        return Vec::new();
+    };
+
+    let mut subtitles: Vec<Subtitle> = fs_objects
+        .iter()
+        .filter(|(_, candidate)| {
+            candidate.path.parent() == Some(parent) && is_subtitle(&candidate.path)
+        })
+        .filter_map(|(&id, candidate)| {
+            let candidate_stem = candidate.path.file_stem()?.to_str()?;
+            let lang = if candidate_stem == stem {
+                ""
+            } else {
+                candidate_stem.strip_prefix(stem)?.strip_prefix('.')?
+            };
+
+            Some(Subtitle {
+                id,
+                lang: lang.to_string(),
+                label: if lang.is_empty() {
+                    "Subtitles".to_string()
+                } else {
+                    lang.to_string()
+                },
+            })
+        })
+        .collect();
+
+    subtitles.sort_by(|a, b| a.label.cmp(&b.label));
+    subtitles
+}
+
+/// Converts SubRip subtitles to WebVTT, the only format supported by `<track>`.
+pub fn srt_to_vtt(srt: &str) -> String {
+    let mut vtt = String::with_capacity(srt.len() + 8);
+    vtt.push_str("WEBVTT\n\n");
+
+    for line in srt.trim_start_matches('\u{feff}').lines() {
+        if line.contains("-->") {
+            vtt.push_str(&line.replace(',', "."));
+        } else {
+            vtt.push_str(line);
+        }
+        vtt.push('\n');
+    }
+
+    vtt
+}
+
+#[derive(Template)]
+#[template(path = "preview/media.html")]
+pub struct MediaTemplate<'a> {
+    pub title: &'a str,
+    pub name: &'a str,
+    pub id: u64,
+    pub size: String,
+    pub mime: &'a str,
+    pub video: bool,
+    pub subtitles: Vec<Subtitle>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_srt_to_vtt() {
+        let srt = "\u{feff}1\r\n00:00:01,000 --> 00:00:02,500\r\nHello, world\r\n\r\n2\r\n00:00:03,000 --> 00:00:04,000\r\nBye\r\n";
+        assert_eq!(
+            srt_to_vtt(srt),
+            "WEBVTT\n\n1\n00:00:01.000 --> 00:00:02.500\nHello, world\n\n2\n00:00:03.000 --> 00:00:04.000\nBye\n"
+        );
+    }
+}
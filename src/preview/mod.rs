@@ -0,0 +1,160 @@
+//! Preview pages for the files distributed in transmitter mode.
+//!
+//! The kind of a file is detected by its extension, and [`PreviewKind`] decides how
+//! `/pw` presents it: some files get a dedicated page, the rest are served inline as is.
+
+pub mod data;
+pub mod document;
+pub mod hex;
+pub mod markdown;
+pub mod media;
+pub mod text;
+
+use crate::fs_object::FsObject;
+use axum::{
+    extract::Request,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use mime::Mime;
+use serde::Deserialize;
+use std::path::Path;
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+
+pub static PREVIEW_JS: &[u8] = include_bytes!("../../templates/preview/preview.js");
+
+/// How a file is presented by the preview handler.
+///
+/// Can be requested explicitly with the `mode` query parameter, e.g. `/pw?id=1&mode=text`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PreviewKind {
+    Video,
+    Audio,
+    Markdown,
+    /// CSV/TSV table.
+    Table,
+    /// JSON/NDJSON tree.
+    Json,
+    /// PDF or office document.
+    Document,
+    Text,
+    /// Paged hex dump.
+    Hex,
+    /// Served inline and left to the browser.
+    Raw,
+}
+
+impl PreviewKind {
+    pub fn detect(path: &Path) -> Self {
+        match media::media_type(path) {
+            Some((media::MediaKind::Video, _)) => PreviewKind::Video,
+            Some((media::MediaKind::Audio, _)) => PreviewKind::Audio,
+            None if document::is_pdf(path) || document::is_office(path) => PreviewKind::Document,
+            None if markdown::is_markdown(path) => PreviewKind::Markdown,
+            None if data::is_table(path) => PreviewKind::Table,
+            None if data::is_json(path) => PreviewKind::Json,
+            None if text::is_text(path) => PreviewKind::Text,
+            // Browsers would only offer to download files of unknown type
+            None if mime_guess::from_path(path)
+                .first()
+                .is_none_or(|mime| mime == mime::APPLICATION_OCTET_STREAM) =>
+            {
+                PreviewKind::Hex
+            }
+            None => PreviewKind::Raw,
+        }
+    }
+
+    /// Like [`PreviewKind::detect`], but files without an extension (`README`, `LICENSE`)
+    /// are previewed as text unless their first bytes look binary.
+    pub async fn detect_with_content(path: &Path) -> Self {
+        let kind = Self::detect(path);
+        if kind != PreviewKind::Hex || path.extension().is_some() {
+            return kind;
+        }
+
+        match read_head(path, 8192).await {
+            Ok(head) if !crate::content_search::is_binary(&head) => PreviewKind::Text,
+            _ => kind,
+        }
+    }
+}
+
+/// Reads up to `len` leading bytes of the file.
+pub async fn read_head(path: &Path, len: u64) -> std::io::Result<Vec<u8>> {
+    use tokio::io::AsyncReadExt;
+
+    let mut head = Vec::new();
+    tokio::fs::File::open(path)
+        .await?
+        .take(len)
+        .read_to_end(&mut head)
+        .await?;
+    Ok(head)
+}
+
+/// Returns the lowercase extension of the path, if any.
+pub fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_ascii_lowercase)
+}
+
+/// Picks the MIME type the file is served with.
+///
+/// Media files get the type the player page announces for them (e.g. `audio/ogg` for
+/// `.opus`), other files the type guessed from the extension.
+pub fn mime_for(path: &Path) -> Mime {
+    media::media_type(path)
+        .and_then(|(_, mime)| mime.parse().ok())
+        .unwrap_or_else(|| mime_guess::from_path(path).first_or_octet_stream())
+}
+
+/// Serves the file inline with the detected MIME type. Supports HTTP range requests.
+pub async fn serve_inline(fs_object: &FsObject, request: Request) -> Result<Response, StatusCode> {
+    let mime = mime_for(&fs_object.path);
+
+    let response = ServeFile::new_with_mime(&fs_object.path, &mime)
+        .oneshot(request)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to serve file for preview: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .into_response();
+
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use axum::{body::Body, http::header};
+
+    #[tokio::test]
+    async fn test_serve_inline_range() {
+        let dir = TempDir::new();
+        let path = dir.join("movie.mkv");
+        std::fs::write(&path, b"0123456789").unwrap();
+        let metadata = std::fs::metadata(&path).unwrap();
+        let fs_object = FsObject::new(path.clone(), metadata, None);
+
+        let request = Request::builder()
+            .uri("/raw")
+            .header(header::RANGE, "bytes=2-5")
+            .body(Body::empty())
+            .unwrap();
+        let response = serve_inline(&fs_object, request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 2-5/10");
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "video/x-matroska");
+        let body = axum::body::to_bytes(response.into_body(), 16)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"2345");
+    }
+}
@@ -0,0 +1,215 @@
+//! Syntax-highlighted preview of text and source files, loaded in chunks.
+
+use super::extension;
+use askama::Template;
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use serde::Serialize;
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+    sync::LazyLock,
+};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{Theme, ThemeSet},
+    html::{styled_line_to_highlighted_html, IncludeBackground},
+    parsing::{SyntaxReference, SyntaxSet},
+    util::LinesWithEndings,
+};
+
+/// Size of the file part rendered per request.
+pub const TEXT_CHUNK_SIZE: u64 = 256 * 1024;
+
+/// Number of leading bytes used to guess the encoding.
+const ENCODING_SNIFF_LEN: usize = 64 * 1024;
+
+const THEME_NAME: &str = "base16-ocean.dark";
+
+/// Extensions of text files unknown to both `mime_guess` and the bundled syntaxes.
+const TEXT_EXTENSIONS: &[&str] = &["log", "conf", "cfg", "ini", "env", "toml", "srt", "vtt"];
+
+static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+
+static THEME: LazyLock<Theme> = LazyLock::new(|| {
+    let mut themes = ThemeSet::load_defaults().themes;
+    themes.remove(THEME_NAME).unwrap_or_default()
+});
+
+/// Checks by name whether the file is a text or source file.
+pub fn is_text(path: &Path) -> bool {
+    let Some(ext) = extension(path) else {
+        return false;
+    };
+
+    TEXT_EXTENSIONS.contains(&ext.as_str())
+        || mime_guess::from_ext(&ext)
+            .first()
+            .is_some_and(|mime| mime.type_() == mime::TEXT)
+        || SYNTAX_SET.find_syntax_by_extension(&ext).is_some()
+}
+
+fn find_syntax(path: &Path) -> &'static SyntaxReference {
+    let by_extension = extension(path).and_then(|ext| SYNTAX_SET.find_syntax_by_extension(&ext));
+    let by_name = || {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| SYNTAX_SET.find_syntax_by_extension(name))
+    };
+
+    by_extension
+        .or_else(by_name)
+        .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text())
+}
+
+/// Detects the text encoding by BOM, UTF-8 validity or statistical guess.
+pub fn detect_encoding(head: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(head) {
+        return encoding;
+    }
+
+    match std::str::from_utf8(head) {
+        Ok(_) => UTF_8,
+        // The sample may end in the middle of a character.
+        Err(err) if err.error_len().is_none() => UTF_8,
+        Err(_) => {
+            let mut detector = EncodingDetector::new();
+            detector.feed(head, false);
+            detector.guess(None, true)
+        }
+    }
+}
+
+/// Returns the length of the data up to and including the last line break.
+fn cut_at_line_end(data: &[u8], encoding: &'static Encoding) -> Option<usize> {
+    if encoding == UTF_16LE {
+        data.windows(2)
+            .rposition(|pair| pair == b"\n\0")
+            .map(|pos| pos + 2)
+    } else if encoding == UTF_16BE {
+        data.windows(2)
+            .rposition(|pair| pair == b"\0\n")
+            .map(|pos| pos + 2)
+    } else {
+        data.iter()
+            .rposition(|&byte| byte == b'\n')
+            .map(|pos| pos + 1)
+    }
+}
+
+/// Highlighted part of a text file.
+#[derive(Serialize)]
+pub struct TextChunk {
+    pub html: String,
+    /// Offset of the next chunk, `None` if the end of the file is reached.
+    pub next_offset: Option<u64>,
+    /// Number of the first line of the next chunk.
+    pub next_line: usize,
+    #[serde(skip)]
+    pub encoding: &'static str,
+}
+
+/// Reads and highlights up to [`TEXT_CHUNK_SIZE`] bytes starting at `offset`,
+/// which must be the beginning of the line numbered `first_line`.
+pub fn render_chunk(
+    path: &Path,
+    file_size: u64,
+    offset: u64,
+    first_line: usize,
+) -> io::Result<TextChunk> {
+    let mut file = File::open(path)?;
+
+    let mut head = Vec::with_capacity(ENCODING_SNIFF_LEN);
+    (&mut file)
+        .take(ENCODING_SNIFF_LEN as u64)
+        .read_to_end(&mut head)?;
+    let encoding = detect_encoding(&head);
+
+    let mut data = Vec::new();
+    file.seek(SeekFrom::Start(offset))?;
+    file.take(TEXT_CHUNK_SIZE).read_to_end(&mut data)?;
+
+    let mut next_offset = None;
+    if offset + (data.len() as u64) < file_size {
+        let len = cut_at_line_end(&data, encoding).unwrap_or(data.len());
+        data.truncate(len);
+        next_offset = Some(offset + len as u64);
+    }
+
+    let text = if offset == 0 {
+        encoding.decode_with_bom_removal(&data).0
+    } else {
+        encoding.decode_without_bom_handling(&data).0
+    };
+
+    let (html, next_line) = highlight(path, &text, first_line);
+
+    Ok(TextChunk {
+        html,
+        next_offset,
+        next_line,
+        encoding: encoding.name(),
+    })
+}
+
+/// Renders every line with its number and an anchor (`#L<number>`).
+fn highlight(path: &Path, text: &str, first_line: usize) -> (String, usize) {
+    let mut highlighter = HighlightLines::new(find_syntax(path), &THEME);
+    let mut html = String::with_capacity(text.len() * 3);
+    let mut line_number = first_line;
+
+    for line in LinesWithEndings::from(text) {
+        let line_html = highlighter
+            .highlight_line(line, &SYNTAX_SET)
+            .and_then(|regions| styled_line_to_highlighted_html(&regions, IncludeBackground::No))
+            .unwrap_or_else(|err| {
+                tracing::debug!("Failed to highlight line {line_number}: {err}");
+                crate::html_page_utils::HtmlEscaped(line).to_string()
+            });
+
+        let _ = write!(
+            html,
+            r##"<span class="line" id="L{line_number}"><a class="line-number" href="#L{line_number}">{line_number}</a>{line_html}</span>"##
+        );
+        line_number += 1;
+    }
+
+    (html, line_number)
+}
+
+#[derive(Template)]
+#[template(path = "preview/text.html")]
+pub struct TextTemplate<'a> {
+    pub title: &'a str,
+    pub name: &'a str,
+    pub id: u64,
+    pub size: String,
+    pub chunk: TextChunk,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::WINDOWS_1251;
+
+    #[test]
+    fn test_detect_encoding() {
+        assert_eq!(detect_encoding(b"plain ascii"), UTF_8);
+        assert_eq!(detect_encoding("привіт".as_bytes()), UTF_8);
+        assert_eq!(detect_encoding(&"привіт".as_bytes()[..5]), UTF_8);
+        assert_eq!(detect_encoding(b"\xff\xfeh\0i\0"), UTF_16LE);
+
+        let (cp1251, _, _) =
+            WINDOWS_1251.encode("Привіт, як справи? Це тестовий текст українською мовою.");
+        assert_eq!(detect_encoding(&cp1251), WINDOWS_1251);
+    }
+
+    #[test]
+    fn test_cut_at_line_end() {
+        assert_eq!(cut_at_line_end(b"one\ntwo\nthr", UTF_8), Some(8));
+        assert_eq!(cut_at_line_end(b"no newline", UTF_8), None);
+        assert_eq!(cut_at_line_end(b"a\0\n\0b\0", UTF_16LE), Some(4));
+    }
+}
@@ -0,0 +1,192 @@
+//! Filename search over the scanned [`FsObject`] tree.
+
+use crate::fs_object::{FsObject, FsObjects};
+use globset::{GlobBuilder, GlobMatcher};
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use std::path::Path;
+
+/// How a search query is interpreted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    /// Case-insensitive substring.
+    #[default]
+    Substring,
+    /// Shell-like wildcard pattern (`*.jpg`, `photos/**/IMG_*`).
+    Glob,
+    /// Regular expression.
+    Regex,
+}
+
+/// Part of an entry the query is matched against.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchScope {
+    #[default]
+    Name,
+    Path,
+}
+
+/// Compiled search query.
+pub enum Matcher {
+    Substring(String),
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+impl Matcher {
+    pub fn new(query: &str, mode: MatchMode) -> Result<Self, String> {
+        match mode {
+            MatchMode::Substring => Ok(Matcher::Substring(query.to_lowercase())),
+            MatchMode::Glob => GlobBuilder::new(query)
+                .case_insensitive(true)
+                .literal_separator(true)
+                .build()
+                .map(|glob| Matcher::Glob(glob.compile_matcher()))
+                .map_err(|err| err.to_string()),
+            MatchMode::Regex => RegexBuilder::new(query)
+                .case_insensitive(true)
+                .size_limit(1 << 20)
+                .build()
+                .map(Matcher::Regex)
+                .map_err(|err| err.to_string()),
+        }
+    }
+
+    /// Checks a single line of text, used by content search.
+    pub fn is_match(&self, text: &str) -> bool {
+        match self {
+            Matcher::Substring(query) => text.to_lowercase().contains(query.as_str()),
+            _ => self.matches(text, ""),
+        }
+    }
+
+    /// Checks the text against the query. `lowercase` must be `text.to_lowercase()`.
+    fn matches(&self, text: &str, lowercase: &str) -> bool {
+        match self {
+            Matcher::Substring(query) => lowercase.contains(query.as_str()),
+            Matcher::Glob(glob) => glob.is_match(text),
+            Matcher::Regex(regex) => regex.is_match(text),
+        }
+    }
+}
+
+/// A single searchable element of the tree.
+pub struct SearchEntry {
+    /// ID used by the download and preview handlers (files only).
+    pub id: u64,
+    /// Path relative to the parent of the shared root, with `/` separators.
+    pub path: Box<str>,
+    path_lowercase: Box<str>,
+    /// Byte offset of the file name within `path`.
+    name_offset: usize,
+    pub is_dir: bool,
+    pub size: u64,
+}
+
+impl SearchEntry {
+    pub fn name(&self) -> &str {
+        &self.path[self.name_offset..]
+    }
+}
+
+/// Flat list of all scanned elements, built once after scanning.
+pub struct SearchIndex {
+    entries: Vec<SearchEntry>,
+}
+
+impl SearchIndex {
+    pub fn build(fs_objects: &FsObjects) -> Self {
+        let mut entries = Vec::new();
+
+        for root in fs_objects {
+            let base = root.path.parent().unwrap_or(Path::new(""));
+            Self::collect(base, root, &mut entries);
+        }
+
+        Self { entries }
+    }
+
+    /// Pre-order traversal, so that results follow the order of the listing.
+    fn collect(base: &Path, item: &FsObject, entries: &mut Vec<SearchEntry>) {
+        entries.push(Self::entry(base, item));
+        for child in item.content.iter().flatten() {
+            Self::collect(base, child, entries);
+        }
+    }
+
+    fn entry(base: &Path, item: &FsObject) -> SearchEntry {
+        let relative = item.path.strip_prefix(base).unwrap_or(&item.path);
+        let path: String = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let name_offset = path.rfind('/').map_or(0, |pos| pos + 1);
+
+        SearchEntry {
+            id: item.get_hash(),
+            path_lowercase: path.to_lowercase().into_boxed_str(),
+            path: path.into_boxed_str(),
+            name_offset,
+            is_dir: item.is_dir(),
+            size: item.size,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn entries(&self) -> &[SearchEntry] {
+        &self.entries
+    }
+
+    /// Returns up to `limit` matching entries in tree order and whether more were available.
+    pub fn search(
+        &self,
+        matcher: &Matcher,
+        scope: MatchScope,
+        limit: usize,
+    ) -> (Vec<&SearchEntry>, bool) {
+        let mut found = self.entries.iter().filter(|entry| match scope {
+            MatchScope::Name => {
+                matcher.matches(entry.name(), &entry.path_lowercase[entry.name_offset..])
+            }
+            MatchScope::Path => matcher.matches(&entry.path, &entry.path_lowercase),
+        });
+
+        let results: Vec<_> = found.by_ref().take(limit).collect();
+        let truncated = found.next().is_some();
+
+        (results, truncated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matcher() {
+        let substring = Matcher::new("Report", MatchMode::Substring).unwrap();
+        assert!(substring.matches("Annual_Report.pdf", "annual_report.pdf"));
+        assert!(!substring.matches("Summary.pdf", "summary.pdf"));
+
+        let glob = Matcher::new("*.JPG", MatchMode::Glob).unwrap();
+        assert!(glob.matches("IMG_001.jpg", ""));
+        assert!(!glob.matches("photos/IMG_001.png", ""));
+        assert!(!glob.matches("photos/IMG_001.jpg", ""));
+
+        let glob = Matcher::new("photos/**/*.jpg", MatchMode::Glob).unwrap();
+        assert!(glob.matches("photos/2024/may/IMG_001.jpg", ""));
+
+        let regex = Matcher::new(r"^img_\d+\.jpe?g$", MatchMode::Regex).unwrap();
+        assert!(regex.matches("IMG_001.jpeg", ""));
+        assert!(!regex.matches("IMG_X.jpeg", ""));
+
+        assert!(Matcher::new("(", MatchMode::Regex).is_err());
+        assert!(Matcher::new("[", MatchMode::Glob).is_err());
+    }
+}
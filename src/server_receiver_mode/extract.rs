@@ -0,0 +1,270 @@
+//! Extraction of received archives into a subdirectory next to them.
+
+use super::{
+    complete_upload,
+    file_type::FileTypeFilter,
+    inbox::INDEX_NAME,
+    manifest::MANIFEST_NAME,
+    register_upload, staging_dir,
+    upload::{is_temp_name, SavedFile},
+    ReceiverState,
+};
+use crate::archive::{self, ArchiveFormat, ExtractLimits, MemberFilter};
+use serde::Serialize;
+use std::{io, net::IpAddr, path::Path, sync::Arc};
+
+pub struct ArchiveExtraction {
+    /// Keep the archive after extracting it.
+    pub keep_archives: bool,
+    pub limits: ExtractLimits,
+}
+
+/// Where an archive was extracted to.
+#[derive(Serialize)]
+pub struct ExtractedArchive {
+    /// Directory relative to the uploads directory.
+    pub dir: String,
+    pub files: usize,
+    pub size: u64,
+    /// Files left out because their type isn't accepted or the hook rejected them.
+    pub skipped: usize,
+    /// Whether the archive itself was kept.
+    pub archive_kept: bool,
+}
+
+/// Extracts a received archive if extraction is enabled and the file is an archive.
+///
+/// Runs before the archive itself is completed: `name` is relative to the uploads
+/// directory and `staged_path` is where the archive was saved. The extracted files go
+/// through the hook and are recorded like uploaded files. Unless archives are kept, the
+/// archive is removed, and the caller must only complete it if `archive_kept` is set.
+/// A failed extraction leaves the archive in place and removes whatever was extracted.
+pub async fn extract_upload(
+    state: &ReceiverState,
+    name: &str,
+    staged_path: &Path,
+    client_ip: IpAddr,
+    session: Option<&str>,
+) -> Option<Result<ExtractedArchive, String>> {
+    let extraction = Arc::clone(state.extraction.as_ref()?);
+    let format = ArchiveFormat::detect(staged_path)?;
+    let keep_archives = extraction.keep_archives;
+
+    let (parent, file_name) = match name.rsplit_once('/') {
+        Some((parent, file_name)) => (format!("{parent}/"), file_name),
+        None => (String::new(), name),
+    };
+    let dir = match create_extraction_dir(&state.uploads_path, &parent, file_name).await {
+        Ok(dir) => dir,
+        Err(err) => {
+            tracing::error!("Failed to create a directory to extract {name} to: {err}");
+            return Some(Err("The archive can't be extracted".into()));
+        }
+    };
+    // In quarantine mode the files are extracted into the staging directory and only
+    // moved into the uploads directory once the hook approves them
+    let dir_path = staging_dir(state).join(&dir);
+    if let Err(err) = tokio::fs::create_dir_all(&dir_path).await {
+        tracing::error!("Failed to create {}: {err}", dir_path.display());
+        remove_extraction_dirs(state, &dir).await;
+        return Some(Err("The archive can't be extracted".into()));
+    }
+
+    let limits = Arc::clone(&state.limits);
+    let file_types = Arc::clone(&state.file_types);
+    let (path, dest, owned_name) = (staged_path.to_owned(), dir_path.clone(), name.to_owned());
+    let result = tokio::task::spawn_blocking(move || {
+        let mut budget = limits.extraction_budget(&owned_name);
+        let extracted = archive::extract(
+            &path,
+            format,
+            &dest,
+            &extraction.limits,
+            &ExtractedNames(&file_types),
+            &mut |len| {
+                budget
+                    .add(len)
+                    .map_err(|err| io::Error::other(err.to_string()))
+            },
+        )?;
+        budget.commit();
+        Ok::<_, io::Error>(extracted)
+    })
+    .await
+    .unwrap_or_else(|err| Err(io::Error::other(err)));
+
+    let extracted = match result {
+        Ok(extracted) => extracted,
+        Err(err) => {
+            tracing::warn!("Failed to extract {name}: {err}");
+            remove_extraction_dirs(state, &dir).await;
+            return Some(Err(format!("The archive can't be extracted: {err}")));
+        }
+    };
+    tracing::info!(
+        "Extracted {} files ({} bytes, {} skipped) from {name} to {}",
+        extracted.files.len(),
+        extracted.size,
+        extracted.skipped,
+        dir_path.display()
+    );
+
+    let (mut files, mut size, mut skipped) = (0, 0, extracted.skipped);
+    for file in extracted.files {
+        let member_name = format!("{dir}/{}", file.path);
+        let saved = SavedFile {
+            size: file.size,
+            sha256: file.sha256,
+        };
+        if let Some(parent) = Path::new(&member_name).parent() {
+            if let Err(err) = tokio::fs::create_dir_all(state.uploads_path.join(parent)).await {
+                tracing::error!("Failed to create a directory for {member_name}: {err}");
+            }
+        }
+
+        let staged_member = dir_path.join(&file.path);
+        match complete_upload(state, &member_name, &staged_member, &saved, client_ip).await {
+            Ok(_) => {
+                register_upload(
+                    state,
+                    &member_name,
+                    &saved,
+                    client_ip,
+                    session.map(Into::into),
+                )
+                .await;
+                files += 1;
+                size += saved.size;
+            }
+            Err(err) => {
+                tracing::warn!("Extracted file not accepted: {err}");
+                skipped += 1;
+            }
+        }
+    }
+    if staging_dir(state) != state.uploads_path.as_path() {
+        if let Err(err) = tokio::fs::remove_dir_all(&dir_path).await {
+            tracing::error!("Failed to remove {}: {err}", dir_path.display());
+        }
+    }
+
+    let archive_kept = keep_archives || !remove_archive(state, name, staged_path).await;
+
+    Some(Ok(ExtractedArchive {
+        dir,
+        files,
+        size,
+        skipped,
+        archive_kept,
+    }))
+}
+
+/// Leaves out members named like unfinished uploads, which the listing hides, and
+/// applies the file type filter to the rest.
+struct ExtractedNames<'a>(&'a FileTypeFilter);
+
+impl MemberFilter for ExtractedNames<'_> {
+    fn accepts_name(&self, name: &str) -> bool {
+        let file_name = name.rsplit('/').next().unwrap_or(name);
+        if is_temp_name(file_name) {
+            tracing::info!("Skipping archive member with a reserved name: {name}");
+            return false;
+        }
+        self.0.accepts_name(name)
+    }
+
+    fn accepts_content(&self, name: &str, head: &[u8]) -> bool {
+        self.0.accepts_content(name, head)
+    }
+}
+
+/// Removes the directories of a failed extraction.
+async fn remove_extraction_dirs(state: &ReceiverState, dir: &str) {
+    for parent in [staging_dir(state), state.uploads_path.as_path()] {
+        let path = parent.join(dir);
+        match tokio::fs::remove_dir_all(&path).await {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                tracing::error!("Failed to remove {}: {err}", path.display());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Creates an empty directory named after the archive, adding a counter if the name is taken.
+/// Returns its path relative to the uploads directory.
+async fn create_extraction_dir(
+    uploads_path: &Path,
+    parent: &str,
+    file_name: &str,
+) -> io::Result<String> {
+    let base_name = extraction_dir_name(file_name);
+
+    let mut suffix = 1;
+    loop {
+        let dir = match suffix {
+            1 => format!("{parent}{base_name}"),
+            _ => format!("{parent}{base_name}-{suffix}"),
+        };
+        if dir != MANIFEST_NAME && dir != INDEX_NAME {
+            match tokio::fs::create_dir(uploads_path.join(&dir)).await {
+                Ok(()) => return Ok(dir),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err),
+            }
+        }
+        suffix += 1;
+    }
+}
+
+/// Deletes an extracted archive before it was completed and returns its space to the quota.
+async fn remove_archive(state: &ReceiverState, name: &str, path: &Path) -> bool {
+    let size = tokio::fs::metadata(path)
+        .await
+        .map_or(0, |metadata| metadata.len());
+    if let Err(err) = tokio::fs::remove_file(path).await {
+        tracing::error!("Failed to remove extracted archive {name}: {err}");
+        return false;
+    }
+    state.limits.release(size);
+
+    // Saved straight into the uploads directory, the archive replaced any earlier file
+    // with its name
+    if path == state.uploads_path.join(name) {
+        state.ledger.remove(name);
+        if let Err(err) = state.manifest.remove(name).await {
+            tracing::error!("Failed to remove checksum of {name}: {err}");
+        }
+    }
+    true
+}
+
+/// The archive name without its extension, e.g. `photos` for `photos.tar.gz`.
+fn extraction_dir_name(file_name: &str) -> &str {
+    let lowercase = file_name.to_ascii_lowercase();
+    let stem_len = [".tar.gz", ".tgz", ".tar", ".zip"]
+        .iter()
+        .find(|extension| lowercase.ends_with(*extension))
+        .map_or(file_name.len(), |extension| {
+            file_name.len() - extension.len()
+        });
+
+    match file_name[..stem_len].trim_end_matches('.') {
+        "" => "archive",
+        stem => stem,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extraction_dir_name() {
+        assert_eq!(extraction_dir_name("photos.zip"), "photos");
+        assert_eq!(extraction_dir_name("Backup.2026.TAR.GZ"), "Backup.2026");
+        assert_eq!(extraction_dir_name("site.tgz"), "site");
+        assert_eq!(extraction_dir_name(".zip"), "archive");
+        assert_eq!(extraction_dir_name("notes"), "notes");
+    }
+}
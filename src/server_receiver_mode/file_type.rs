@@ -0,0 +1,181 @@
+//! Extension and content-sniffed MIME type allow/deny lists for received files.
+
+use super::upload::UploadError;
+use crate::archive::MemberFilter;
+use std::path::Path;
+
+/// MIME type of content that [`infer`] doesn't recognize and that looks binary.
+const UNKNOWN_BINARY_MIME: &str = "application/octet-stream";
+
+/// MIME type of content that [`infer`] doesn't recognize and that looks like text.
+const UNKNOWN_TEXT_MIME: &str = "text/plain";
+
+/// Which files may be received. A deny list entry always wins; a non-empty allow list
+/// admits only the files it matches.
+#[derive(Debug, Default)]
+pub struct FileTypeFilter {
+    /// Lowercase extensions without the leading dot.
+    pub allowed_extensions: Vec<String>,
+    pub denied_extensions: Vec<String>,
+    /// MIME types, `type/*` matches the whole type.
+    pub allowed_mime_types: Vec<String>,
+    pub denied_mime_types: Vec<String>,
+}
+
+impl FileTypeFilter {
+    pub fn new(
+        allowed_extensions: &[String],
+        denied_extensions: &[String],
+        allowed_mime_types: &[String],
+        denied_mime_types: &[String],
+    ) -> Self {
+        let normalize = |items: &[String]| -> Vec<String> {
+            items
+                .iter()
+                .map(|item| item.trim().trim_start_matches('.').to_ascii_lowercase())
+                .filter(|item| !item.is_empty())
+                .collect()
+        };
+
+        Self {
+            allowed_extensions: normalize(allowed_extensions),
+            denied_extensions: normalize(denied_extensions),
+            allowed_mime_types: normalize(allowed_mime_types),
+            denied_mime_types: normalize(denied_mime_types),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allowed_extensions.is_empty()
+            && self.denied_extensions.is_empty()
+            && self.allowed_mime_types.is_empty()
+            && self.denied_mime_types.is_empty()
+    }
+
+    /// Checks the extension of the file name.
+    pub fn check_name(&self, name: &str) -> Result<(), UploadError> {
+        let extension = Path::new(name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase)
+            .unwrap_or_default();
+
+        let denied = self.denied_extensions.contains(&extension);
+        let allowed =
+            self.allowed_extensions.is_empty() || self.allowed_extensions.contains(&extension);
+
+        if denied || !allowed {
+            return Err(UploadError::UnsupportedType {
+                name: name.to_owned(),
+                reason: if extension.is_empty() {
+                    "files without an extension are not accepted".into()
+                } else {
+                    format!(".{extension} files are not accepted")
+                },
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Checks the MIME type sniffed from the first bytes of the file.
+    pub fn check_content(&self, name: &str, head: &[u8]) -> Result<(), UploadError> {
+        if self.allowed_mime_types.is_empty() && self.denied_mime_types.is_empty() {
+            return Ok(());
+        }
+
+        let mime = sniff_mime(head);
+        let matches =
+            |patterns: &[String]| patterns.iter().any(|pattern| mime_matches(pattern, mime));
+
+        let denied = matches(&self.denied_mime_types);
+        let allowed = self.allowed_mime_types.is_empty() || matches(&self.allowed_mime_types);
+
+        if denied || !allowed {
+            return Err(UploadError::UnsupportedType {
+                name: name.to_owned(),
+                reason: format!("{mime} content is not accepted"),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Archive members are held to the same rules as uploaded files.
+impl MemberFilter for FileTypeFilter {
+    fn accepts_name(&self, name: &str) -> bool {
+        accepts_member(self.check_name(name))
+    }
+
+    fn accepts_content(&self, name: &str, head: &[u8]) -> bool {
+        accepts_member(self.check_content(name, head))
+    }
+}
+
+fn accepts_member(check: Result<(), UploadError>) -> bool {
+    match check {
+        Ok(()) => true,
+        Err(err) => {
+            tracing::info!("Skipping archive member: {err}");
+            false
+        }
+    }
+}
+
+/// Detects the MIME type by the magic bytes, distinguishing unknown text from binary data.
+pub fn sniff_mime(head: &[u8]) -> &'static str {
+    match infer::get(head) {
+        Some(kind) => kind.mime_type(),
+        None if head.is_empty() || !crate::content_search::is_binary(head) => UNKNOWN_TEXT_MIME,
+        None => UNKNOWN_BINARY_MIME,
+    }
+}
+
+fn mime_matches(pattern: &str, mime: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(type_) => mime
+            .split_once('/')
+            .is_some_and(|(mime_type, _)| mime_type == type_),
+        None => pattern == mime,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|item| item.to_string()).collect()
+    }
+
+    #[test]
+    fn test_check_name() {
+        let filter = FileTypeFilter::new(&[], &strings(&[".EXE", "bat"]), &[], &[]);
+        assert!(filter.check_name("report.pdf").is_ok());
+        assert!(filter.check_name("setup.exe").is_err());
+        assert!(filter.check_name("run.BAT").is_err());
+
+        let filter = FileTypeFilter::new(&strings(&["pdf", "docx"]), &[], &[], &[]);
+        assert!(filter.check_name("report.PDF").is_ok());
+        assert!(filter.check_name("notes.txt").is_err());
+        assert!(filter.check_name("Makefile").is_err());
+    }
+
+    #[test]
+    fn test_check_content() {
+        let mut elf = b"\x7fELF\x02\x01\x01".to_vec();
+        elf.resize(64, 0);
+        let pdf = b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n";
+
+        let filter = FileTypeFilter::new(&[], &[], &[], &strings(&["application/x-executable"]));
+        assert!(filter.check_content("a.pdf", pdf).is_ok());
+        assert!(filter.check_content("a.pdf", &elf).is_err());
+
+        let filter = FileTypeFilter::new(&[], &[], &strings(&["application/pdf", "text/*"]), &[]);
+        assert!(filter.check_content("a.pdf", pdf).is_ok());
+        assert!(filter.check_content("a.txt", b"plain text").is_ok());
+        assert!(filter.check_content("a.pdf", &elf).is_err());
+        assert!(filter.check_content("a.bin", b"\x00\x01\x02").is_err());
+    }
+}
@@ -0,0 +1,125 @@
+//! User command run for every received file, optionally deciding whether the file
+//! is accepted (quarantine mode).
+
+use super::upload::temp_path;
+use std::{
+    io,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    process::{ExitStatus, Stdio},
+    time::Duration,
+};
+use tokio::process::Command;
+
+/// Hooks running longer than this are killed and count as failed.
+const HOOK_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// A received file as described to the hook.
+pub struct UploadedFile<'a> {
+    pub path: &'a Path,
+    pub name: &'a str,
+    pub size: u64,
+    pub sha256: &'a str,
+    pub client_ip: IpAddr,
+}
+
+pub struct UploadHook {
+    /// Shell command line.
+    pub command: String,
+    /// Staging directory for files awaiting the hook's verdict in quarantine mode.
+    pub quarantine_dir: Option<PathBuf>,
+}
+
+impl UploadHook {
+    /// Runs the command through the shell with the file described in `MINICLOUD_*`
+    /// environment variables, and waits for it to finish.
+    pub async fn run(&self, file: &UploadedFile<'_>) -> io::Result<ExitStatus> {
+        let mut command = shell_command(&self.command);
+        command
+            .env("MINICLOUD_FILE_PATH", file.path)
+            .env("MINICLOUD_FILE_NAME", file.name)
+            .env("MINICLOUD_FILE_SIZE", file.size.to_string())
+            .env("MINICLOUD_FILE_SHA256", file.sha256)
+            .env("MINICLOUD_CLIENT_IP", file.client_ip.to_string())
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+
+        let mut child = command.spawn()?;
+        match tokio::time::timeout(HOOK_TIMEOUT, child.wait()).await {
+            Ok(status) => status,
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("the hook didn't finish in {} s", HOOK_TIMEOUT.as_secs()),
+            )),
+        }
+    }
+
+    /// Runs the hook and reports whether it exited successfully, logging failures.
+    pub async fn approves(&self, file: &UploadedFile<'_>) -> bool {
+        match self.run(file).await {
+            Ok(status) if status.success() => true,
+            Ok(status) => {
+                tracing::warn!("Upload hook rejected {}: {status}", file.path.display());
+                false
+            }
+            Err(err) => {
+                tracing::error!("Upload hook failed for {}: {err}", file.path.display());
+                false
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn shell_command(command_line: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(command_line);
+    command
+}
+
+#[cfg(windows)]
+fn shell_command(command_line: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(command_line);
+    command
+}
+
+/// Moves the file, copying it when the directories are on different filesystems.
+pub async fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if tokio::fs::rename(from, to).await.is_ok() {
+        return Ok(());
+    }
+
+    let tmp_path = temp_path(to);
+    tokio::fs::copy(from, &tmp_path).await?;
+    tokio::fs::rename(&tmp_path, to).await?;
+    tokio::fs::remove_file(from).await
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_hook_environment() {
+        let file = UploadedFile {
+            path: Path::new("/tmp/report.pdf"),
+            name: "report.pdf",
+            size: 42,
+            sha256: "abc",
+            client_ip: "192.168.1.5".parse().unwrap(),
+        };
+
+        let hook = UploadHook {
+            command: r#"test "$MINICLOUD_FILE_NAME:$MINICLOUD_FILE_SIZE:$MINICLOUD_CLIENT_IP" = "report.pdf:42:192.168.1.5""#.into(),
+            quarantine_dir: None,
+        };
+        assert!(hook.approves(&file).await);
+
+        let hook = UploadHook {
+            command: "exit 3".into(),
+            quarantine_dir: None,
+        };
+        assert!(!hook.approves(&file).await);
+    }
+}
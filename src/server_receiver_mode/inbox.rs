@@ -0,0 +1,202 @@
+//! Per-sender subdirectories of the uploads directory, with an index of who sent what.
+
+use super::{
+    limits::UploadLimits, listing::format_timestamp, manifest::MANIFEST_NAME, upload::UploadError,
+};
+use std::{
+    io,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{io::AsyncWriteExt, sync::Mutex};
+
+/// CSV index of the received files in the uploads directory.
+pub const INDEX_NAME: &str = "INDEX.csv";
+
+const MAX_SENDER_LENGTH: usize = 64;
+
+pub struct Inboxes {
+    /// Inboxes senders pick from. Any sender name is accepted if empty.
+    pub names: Vec<String>,
+    index_path: PathBuf,
+    /// Serializes appends of concurrent uploads.
+    lock: Mutex<()>,
+    /// The index counts towards the uploads quota.
+    limits: Arc<UploadLimits>,
+}
+
+/// A received file as recorded in the index.
+pub struct IndexEntry<'a> {
+    pub inbox: &'a str,
+    pub name: &'a str,
+    pub size: u64,
+    pub sha256: &'a str,
+    pub client_ip: IpAddr,
+}
+
+impl Inboxes {
+    pub fn new(uploads_path: &Path, names: &[String], limits: Arc<UploadLimits>) -> Self {
+        Self {
+            names: names
+                .iter()
+                .map(|name| name.trim().to_owned())
+                .filter(|name| {
+                    let valid = is_valid_inbox_name(name);
+                    if !valid {
+                        tracing::warn!("Invalid inbox name {name:?}. Skipping...");
+                    }
+                    !name.is_empty() && valid
+                })
+                .collect(),
+            index_path: uploads_path.join(INDEX_NAME),
+            lock: Mutex::new(()),
+            limits,
+        }
+    }
+
+    /// Validates the sender name and returns the subdirectory its files are saved to.
+    pub fn inbox_for(&self, sender: &str) -> Result<String, UploadError> {
+        let sender = sender.trim();
+
+        if !self.names.is_empty() {
+            return self
+                .names
+                .iter()
+                .find(|name| name.as_str() == sender)
+                .cloned()
+                .ok_or_else(|| UploadError::BadRequest(format!("Unknown inbox: {sender}")));
+        }
+
+        if sender.is_empty() {
+            return Err(UploadError::BadRequest("Enter your name".into()));
+        }
+        if sender.chars().count() > MAX_SENDER_LENGTH || !is_valid_inbox_name(sender) {
+            return Err(UploadError::BadRequest(format!(
+                "{sender} can't be used as a name"
+            )));
+        }
+
+        Ok(sender.to_owned())
+    }
+
+    /// Appends the file to the index, writing the header first if the index is new.
+    pub async fn record(&self, entry: &IndexEntry<'_>) -> io::Result<()> {
+        let _guard = self.lock.lock().await;
+
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.index_path)
+            .await?;
+        let is_new = file.metadata().await?.len() == 0;
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let rows = index_rows(entry, time, is_new)?;
+        file.write_all(&rows).await?;
+        self.limits.resize(0, rows.len() as u64);
+        Ok(())
+    }
+}
+
+/// Inbox names become directory names, so they must be a single path component.
+fn is_valid_inbox_name(name: &str) -> bool {
+    name != MANIFEST_NAME
+        && name != INDEX_NAME
+        && name != "."
+        && name != ".."
+        && !name.chars().any(|c| {
+            c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|')
+        })
+}
+
+fn index_rows(entry: &IndexEntry<'_>, time: u64, with_header: bool) -> io::Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    if with_header {
+        writer.write_record(["time", "sender", "file", "size", "sha256", "client_ip"])?;
+    }
+    writer.write_record([
+        format_timestamp(time),
+        spreadsheet_safe(entry.inbox),
+        spreadsheet_safe(entry.name),
+        entry.size.to_string(),
+        entry.sha256.to_owned(),
+        entry.client_ip.to_string(),
+    ])?;
+    writer
+        .into_inner()
+        .map_err(|err| io::Error::other(err.to_string()))
+}
+
+/// Prefixes a cell that a spreadsheet would run as a formula with `'`, so a sender
+/// named `=HYPERLINK(...)` stays plain text when the index is opened in Excel.
+fn spreadsheet_safe(cell: &str) -> String {
+    if cell.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{cell}")
+    } else {
+        cell.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs_object::SizeUnits;
+
+    #[test]
+    fn test_inbox_for() {
+        let limits = Arc::new(UploadLimits::new(
+            "uploads".into(),
+            None,
+            None,
+            None,
+            SizeUnits::Binary,
+        ));
+        let inboxes = Inboxes::new(Path::new("uploads"), &[], Arc::clone(&limits));
+        assert_eq!(
+            inboxes.inbox_for(" Anna Kovalenko ").unwrap(),
+            "Anna Kovalenko"
+        );
+        assert!(inboxes.inbox_for("").is_err());
+        assert!(inboxes.inbox_for("..").is_err());
+        assert!(inboxes.inbox_for("../etc").is_err());
+        assert!(inboxes.inbox_for(INDEX_NAME).is_err());
+
+        let inboxes = Inboxes::new(
+            Path::new("uploads"),
+            &["Group A".into(), "Group B".into()],
+            limits,
+        );
+        assert_eq!(inboxes.inbox_for("Group B").unwrap(), "Group B");
+        assert!(inboxes.inbox_for("Group C").is_err());
+    }
+
+    #[test]
+    fn test_index_rows() {
+        let entry = IndexEntry {
+            inbox: "Anna",
+            name: "essay, final.docx",
+            size: 42,
+            sha256: "abc",
+            client_ip: "192.168.1.5".parse().unwrap(),
+        };
+        assert_eq!(
+            String::from_utf8(index_rows(&entry, 0, true).unwrap()).unwrap(),
+            "time,sender,file,size,sha256,client_ip\n\
+             1970-01-01 00:00 UTC,Anna,\"essay, final.docx\",42,abc,192.168.1.5\n"
+        );
+
+        let entry = IndexEntry {
+            inbox: "=HYPERLINK(\"http://x\")",
+            name: "-notes.txt",
+            ..entry
+        };
+        assert_eq!(
+            String::from_utf8(index_rows(&entry, 0, false).unwrap()).unwrap(),
+            "1970-01-01 00:00 UTC,\"'=HYPERLINK(\"\"http://x\"\")\",'-notes.txt,42,abc,192.168.1.5\n"
+        );
+    }
+}
@@ -0,0 +1,51 @@
+//! Who uploaded which file, kept in memory while the server runs.
+
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+#[derive(Clone, Debug)]
+pub struct UploadRecord {
+    pub uploader: IpAddr,
+    /// Session of the uploading browser, if it had one.
+    pub session: Option<String>,
+    pub time: SystemTime,
+}
+
+impl UploadRecord {
+    /// Checks whether the session uploaded the file no longer than `window` ago.
+    pub fn is_owned_by(&self, session: Option<&str>, window: Duration) -> bool {
+        session.is_some()
+            && self.session.as_deref() == session
+            && self.time.elapsed().is_ok_and(|elapsed| elapsed <= window)
+    }
+}
+
+/// Upload records by file name relative to the uploads directory.
+#[derive(Default)]
+pub struct UploadLedger {
+    records: Mutex<HashMap<String, UploadRecord>>,
+}
+
+impl UploadLedger {
+    pub fn record(&self, name: &str, record: UploadRecord) {
+        self.lock().insert(name.to_owned(), record);
+    }
+
+    pub fn get(&self, name: &str) -> Option<UploadRecord> {
+        self.lock().get(name).cloned()
+    }
+
+    pub fn remove(&self, name: &str) {
+        self.lock().remove(name);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, UploadRecord>> {
+        self.records
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
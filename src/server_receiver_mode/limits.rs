@@ -0,0 +1,255 @@
+//! Per-file size limit, quota of the uploads directory and free disk space guard.
+
+use super::upload::UploadError;
+use crate::fs_object::SizeUnits;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// How many bytes of a file are written between free space checks.
+const FREE_SPACE_CHECK_INTERVAL: u64 = 16 * 1024 * 1024;
+
+pub struct UploadLimits {
+    uploads_path: PathBuf,
+    /// Maximum size of a single file in bytes.
+    pub max_file_size: Option<u64>,
+    /// Maximum total size of the uploads directory in bytes.
+    pub quota: Option<u64>,
+    /// Free disk space in bytes that uploads must leave untouched.
+    pub min_free_space: Option<u64>,
+    /// Units the limits are reported in.
+    pub units: SizeUnits,
+    /// Bytes stored in the uploads directory, including files being written.
+    used: AtomicU64,
+}
+
+impl UploadLimits {
+    /// Creates the limits, measuring the current size of the uploads directory if a quota is set.
+    pub fn new(
+        uploads_path: PathBuf,
+        max_file_size: Option<u64>,
+        quota: Option<u64>,
+        min_free_space: Option<u64>,
+        units: SizeUnits,
+    ) -> Self {
+        let used = match quota {
+            Some(_) => dir_size(&uploads_path),
+            None => 0,
+        };
+
+        if min_free_space.is_some() {
+            if let Err(err) = available_space(&uploads_path) {
+                tracing::warn!("Free disk space can't be checked, the guard is disabled: {err}");
+            }
+        }
+
+        Self {
+            uploads_path,
+            max_file_size,
+            quota,
+            min_free_space,
+            units,
+            used: AtomicU64::new(used),
+        }
+    }
+
+    pub fn used(&self) -> u64 {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// Checks that the disk has more than the minimum free space left.
+    pub fn check_free_space(&self, incoming: u64) -> Result<(), UploadError> {
+        let Some(min_free_space) = self.min_free_space else {
+            return Ok(());
+        };
+
+        match available_space(&self.uploads_path) {
+            Ok(available) if available < min_free_space.saturating_add(incoming) => {
+                tracing::warn!("Upload rejected: only {available} bytes of free disk space left");
+                Err(UploadError::DiskFull)
+            }
+            Ok(_) => Ok(()),
+            Err(err) => {
+                tracing::debug!("Failed to query free disk space: {err}");
+                Ok(())
+            }
+        }
+    }
+
+    /// Starts accounting the bytes of a new file.
+    pub fn file_budget<'a>(&'a self, name: &'a str) -> FileBudget<'a> {
+        self.budget(name, self.max_file_size)
+    }
+
+    /// Starts accounting the files extracted from an archive. Only the quota and the
+    /// free space apply, not the per-file limit.
+    pub fn extraction_budget<'a>(&'a self, name: &'a str) -> FileBudget<'a> {
+        self.budget(name, None)
+    }
+
+    fn budget<'a>(&'a self, name: &'a str, max_size: Option<u64>) -> FileBudget<'a> {
+        FileBudget {
+            limits: self,
+            name,
+            max_size,
+            written: 0,
+            next_space_check: FREE_SPACE_CHECK_INTERVAL,
+            committed: false,
+        }
+    }
+
+    /// Accounts for a file written outside a [`FileBudget`], such as the manifest, whose
+    /// size changed from `old` to `new` bytes. It is counted even beyond the quota.
+    pub fn resize(&self, old: u64, new: u64) {
+        if new >= old {
+            self.used.fetch_add(new - old, Ordering::Relaxed);
+        } else {
+            self.release(old - new);
+        }
+    }
+
+    /// Returns the space of a removed or overwritten file to the quota.
+    pub fn release(&self, bytes: u64) {
+        let _ = self
+            .used
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+                Some(used.saturating_sub(bytes))
+            });
+    }
+}
+
+/// Bytes of a file being received. They are returned to the quota when the budget
+/// is dropped without [`FileBudget::commit`], i.e. if the upload fails.
+pub struct FileBudget<'a> {
+    limits: &'a UploadLimits,
+    name: &'a str,
+    max_size: Option<u64>,
+    written: u64,
+    next_space_check: u64,
+    committed: bool,
+}
+
+impl FileBudget<'_> {
+    /// Accounts for the next chunk before it is written.
+    pub fn add(&mut self, len: u64) -> Result<(), UploadError> {
+        let limits = self.limits;
+        let written = self.written + len;
+
+        if let Some(limit) = self.max_size.filter(|&limit| written > limit) {
+            return Err(UploadError::TooLarge {
+                name: self.name.to_owned(),
+                limit,
+                units: limits.units,
+            });
+        }
+
+        let used = limits.used.fetch_add(len, Ordering::Relaxed) + len;
+        self.written = written;
+        if let Some(limit) = limits.quota.filter(|&limit| used > limit) {
+            return Err(UploadError::QuotaExceeded {
+                limit,
+                units: limits.units,
+            });
+        }
+
+        if written >= self.next_space_check {
+            self.next_space_check = written + FREE_SPACE_CHECK_INTERVAL;
+            limits.check_free_space(0)?;
+        }
+
+        Ok(())
+    }
+
+    /// Keeps the bytes accounted, the file has been saved.
+    pub fn commit(mut self) {
+        self.committed = true;
+    }
+}
+
+impl Drop for FileBudget<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            self.limits.release(self.written);
+        }
+    }
+}
+
+/// Total size of the files in the directory, recursively.
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| Some((entry.path(), entry.metadata().ok()?)))
+        .map(|(path, metadata)| {
+            if metadata.is_dir() {
+                dir_size(&path)
+            } else {
+                metadata.len()
+            }
+        })
+        .sum()
+}
+
+#[cfg(unix)]
+fn available_space(path: &Path) -> io::Result<u64> {
+    let stat = rustix::fs::statvfs(path)?;
+    Ok(stat.f_bavail.saturating_mul(stat.f_frsize))
+}
+
+#[cfg(not(unix))]
+fn available_space(_path: &Path) -> io::Result<u64> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "free space query is not supported on this platform",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_file_budget() {
+        let dir = TempDir::new();
+        std::fs::write(dir.join("existing"), [0; 20]).unwrap();
+
+        let limits = UploadLimits::new(
+            dir.path().to_owned(),
+            Some(100),
+            Some(150),
+            None,
+            SizeUnits::Binary,
+        );
+        assert_eq!(limits.used(), 20);
+
+        let mut budget = limits.file_budget("a");
+        budget.add(60).unwrap();
+        budget.add(40).unwrap();
+        assert!(matches!(budget.add(1), Err(UploadError::TooLarge { .. })));
+        drop(budget);
+        assert_eq!(limits.used(), 20);
+
+        let mut budget = limits.file_budget("b");
+        budget.add(100).unwrap();
+        budget.commit();
+        assert_eq!(limits.used(), 120);
+
+        let mut budget = limits.file_budget("c");
+        assert!(matches!(
+            budget.add(40),
+            Err(UploadError::QuotaExceeded { .. })
+        ));
+        drop(budget);
+        assert_eq!(limits.used(), 120);
+
+        limits.resize(0, 10);
+        limits.resize(10, 4);
+        assert_eq!(limits.used(), 124);
+    }
+}
@@ -0,0 +1,280 @@
+//! Page listing the received files, where uploaders can download or delete their own
+//! files for a while after uploading them.
+
+use super::{
+    inbox::INDEX_NAME,
+    manifest::MANIFEST_NAME,
+    session::{new_session_id, session_cookie, session_id},
+    upload::{is_temp_name, sanitize_filename},
+    ReceiverState, APP_TITLE,
+};
+use crate::fs_object::SizeFormatter;
+use askama::Template;
+use axum::{
+    extract::{Query, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use serde::Deserialize;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+
+pub struct UploadedEntry {
+    pub name: String,
+    pub size: String,
+    pub modified: String,
+    pub uploader: String,
+    /// The current session may download and delete the file.
+    pub manageable: bool,
+}
+
+#[derive(Template)]
+#[template(path = "server_receiver_mode/uploads.html")]
+struct UploadsTemplate<'a> {
+    title: &'a str,
+    entries: Vec<UploadedEntry>,
+    /// Minutes uploaders may manage their files for, if allowed.
+    access_minutes: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct FileParams {
+    name: String,
+}
+
+/// Lists the received files, newest first.
+pub async fn uploads_page(
+    State(state): State<ReceiverState>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    tracing::info!("Uploads listing request");
+
+    let session = session_id(&headers);
+
+    let mut files = list_files(&state.uploads_path).await.map_err(|err| {
+        tracing::error!("Failed to read uploads directory: {err}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    files.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+
+    let entries = files
+        .into_iter()
+        .map(|(name, size, modified)| {
+            let record = state.ledger.get(&name);
+            let manageable = match (&record, state.uploader_access) {
+                (Some(record), Some(window)) => record.is_owned_by(session.as_deref(), window),
+                _ => false,
+            };
+            UploadedEntry {
+                uploader: match &record {
+                    Some(_) if manageable => "you".to_owned(),
+                    Some(record) => record.uploader.to_string(),
+                    None => "unknown".to_owned(),
+                },
+                size: SizeFormatter(size, state.size_units).to_string(),
+                modified: format_timestamp(modified),
+                name,
+                manageable,
+            }
+        })
+        .collect();
+
+    let page = UploadsTemplate {
+        title: APP_TITLE,
+        entries,
+        access_minutes: state.uploader_access.map(|window| window.as_secs() / 60),
+    }
+    .render()
+    .map_err(|err| {
+        tracing::error!("Template render error: {err}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(with_session(session, Html(page)))
+}
+
+/// Serves a file to the session that uploaded it.
+pub async fn download_own_upload(
+    State(state): State<ReceiverState>,
+    Query(params): Query<FileParams>,
+    request: Request,
+) -> Result<Response, StatusCode> {
+    let path = owned_upload_path(&state, request.headers(), &params.name)?;
+
+    tracing::info!("Uploader download request: {}", path.display());
+
+    let mut response = ServeFile::new(&path)
+        .oneshot(request)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to serve uploaded file: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .into_response();
+
+    if let Ok(value) = HeaderValue::try_from(format!(
+        "attachment; filename*=UTF-8''{}",
+        percent_encoding::utf8_percent_encode(
+            params.name.rsplit('/').next().unwrap_or_default(),
+            percent_encoding::NON_ALPHANUMERIC
+        )
+    )) {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_DISPOSITION, value);
+    }
+
+    Ok(response)
+}
+
+/// Deletes a file on behalf of the session that uploaded it.
+pub async fn delete_own_upload(
+    State(state): State<ReceiverState>,
+    headers: HeaderMap,
+    Form(params): Form<FileParams>,
+) -> Result<Redirect, StatusCode> {
+    let path = owned_upload_path(&state, &headers, &params.name)?;
+
+    let size = tokio::fs::metadata(&path)
+        .await
+        .map_or(0, |metadata| metadata.len());
+    tokio::fs::remove_file(&path).await.map_err(|err| {
+        tracing::error!("Failed to delete {}: {err}", path.display());
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    state.limits.release(size);
+    state.ledger.remove(&params.name);
+    if let Err(err) = state.manifest.remove(&params.name).await {
+        tracing::error!("Failed to remove checksum of {}: {err}", params.name);
+    }
+
+    tracing::info!("Deleted by uploader: {}", path.display());
+
+    Ok(Redirect::to("/uploads"))
+}
+
+/// How many directories deep the listing looks for files.
+const MAX_LISTING_DEPTH: usize = 8;
+
+/// Lists the received files with their sizes and modification times, including the files
+/// in subdirectories such as inboxes and extracted archives, which are named `dir/file`.
+async fn list_files(uploads_path: &Path) -> io::Result<Vec<(String, u64, u64)>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![(String::new(), uploads_path.to_path_buf())];
+
+    while let Some((prefix, dir)) = dirs.pop() {
+        let mut read_dir = match tokio::fs::read_dir(&dir).await {
+            Ok(read_dir) => read_dir,
+            Err(err) if prefix.is_empty() => return Err(err),
+            Err(_) => continue,
+        };
+        while let Ok(Some(entry)) = read_dir.next_entry().await {
+            let Ok(metadata) = entry.metadata().await else {
+                continue;
+            };
+            let name = format!("{prefix}{}", entry.file_name().to_string_lossy());
+            if metadata.is_dir() && prefix.matches('/').count() < MAX_LISTING_DEPTH {
+                dirs.push((format!("{name}/"), entry.path()));
+            }
+            if !metadata.is_file() || !is_listed(&name) {
+                continue;
+            }
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |duration| duration.as_secs());
+            files.push((name, metadata.len(), modified));
+        }
+    }
+
+    Ok(files)
+}
+
+/// Resolves the file if the request's session uploaded it within the allowed time.
+fn owned_upload_path(
+    state: &ReceiverState,
+    headers: &HeaderMap,
+    name: &str,
+) -> Result<PathBuf, StatusCode> {
+    let window = state.uploader_access.ok_or(StatusCode::FORBIDDEN)?;
+
+    let mut components = name.split('/');
+    let is_valid = components.clone().count() <= MAX_LISTING_DEPTH + 1
+        && components.all(|component| sanitize_filename(Some(component)) == component);
+    if !is_valid || !is_listed(name) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let record = state.ledger.get(name).ok_or(StatusCode::FORBIDDEN)?;
+    if !record.is_owned_by(session_id(headers).as_deref(), window) {
+        tracing::warn!("Uploader access denied: {name}");
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    Ok(state.uploads_path.join(name))
+}
+
+/// Hides the checksum manifest, the inbox index and unfinished uploads.
+fn is_listed(name: &str) -> bool {
+    let file_name = name.rsplit('/').next().unwrap_or(name);
+    name != MANIFEST_NAME && name != INDEX_NAME && !is_temp_name(file_name)
+}
+
+/// Adds a new session cookie to the response unless the request already has a session.
+pub fn with_session(session: Option<String>, response: impl IntoResponse) -> Response {
+    let mut response = response.into_response();
+    if session.is_none() {
+        response
+            .headers_mut()
+            .insert(header::SET_COOKIE, session_cookie(&new_session_id()));
+    }
+    response
+}
+
+/// Formats seconds since the Unix epoch as `YYYY-MM-DD HH:MM UTC`.
+pub fn format_timestamp(secs: u64) -> String {
+    let (year, month, day) = civil_date(secs);
+    let (hours, minutes) = (secs % 86_400 / 3600, secs % 3600 / 60);
+    format!("{year:04}-{month:02}-{day:02} {hours:02}:{minutes:02} UTC")
+}
+
+/// Formats seconds since the Unix epoch as `YYYYMMDD-HHMMSS` (UTC) for file names.
+pub fn file_timestamp(secs: u64) -> String {
+    let (year, month, day) = civil_date(secs);
+    let (hours, minutes, seconds) = (secs % 86_400 / 3600, secs % 3600 / 60, secs % 60);
+    format!("{year:04}{month:02}{day:02}-{hours:02}{minutes:02}{seconds:02}")
+}
+
+/// Civil date of the Unix timestamp (Howard Hinnant's algorithm).
+fn civil_date(secs: u64) -> (i64, i64, i64) {
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00 UTC");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29 00:00 UTC");
+        assert_eq!(format_timestamp(1_792_377_802), "2026-10-19 02:43 UTC");
+        assert_eq!(file_timestamp(1_792_377_802), "20261019-024322");
+    }
+}
@@ -0,0 +1,104 @@
+//! `SHA256SUMS` manifest of the received files, verifiable with `sha256sum -c`.
+
+use super::{limits::UploadLimits, upload::temp_path};
+use crate::checksum::{escape_manifest_path, manifest_line};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::Mutex;
+
+pub const MANIFEST_NAME: &str = "SHA256SUMS";
+
+pub struct Manifest {
+    path: PathBuf,
+    /// Serializes the read-modify-write of concurrent uploads.
+    lock: Mutex<()>,
+    /// The manifest counts towards the uploads quota.
+    limits: Arc<UploadLimits>,
+}
+
+impl Manifest {
+    pub fn new(uploads_path: &Path, limits: Arc<UploadLimits>) -> Self {
+        Self {
+            limits,
+            path: uploads_path.join(MANIFEST_NAME),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Records the checksum of a received file, replacing the entry of a previous file
+    /// with the same name. `name` is relative to the uploads directory.
+    pub async fn record(&self, name: &str, sha256: &str) -> io::Result<()> {
+        self.rewrite(name, Some(sha256)).await
+    }
+
+    /// Removes the entry of a deleted file.
+    pub async fn remove(&self, name: &str) -> io::Result<()> {
+        self.rewrite(name, None).await
+    }
+
+    async fn rewrite(&self, name: &str, sha256: Option<&str>) -> io::Result<()> {
+        let _guard = self.lock.lock().await;
+
+        let existing = match tokio::fs::read_to_string(&self.path).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound && sha256.is_none() => return Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err),
+        };
+
+        let mut content = without_entry(&existing, name);
+        if let Some(sha256) = sha256 {
+            manifest_line(&mut content, sha256, name);
+        }
+
+        let tmp_path = temp_path(&self.path);
+        tokio::fs::write(&tmp_path, &content).await?;
+        tokio::fs::rename(&tmp_path, &self.path).await?;
+        self.limits
+            .resize(existing.len() as u64, content.len() as u64);
+        Ok(())
+    }
+}
+
+/// Returns the manifest without the entry for `name`.
+fn without_entry(manifest: &str, name: &str) -> String {
+    let name = escape_manifest_path(name);
+    manifest
+        .lines()
+        .filter(|line| line.split_once("  ").map(|(_, entry)| entry) != Some(&*name))
+        .fold(
+            String::with_capacity(manifest.len()),
+            |mut content, line| {
+                content.push_str(line);
+                content.push('\n');
+                content
+            },
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_without_entry() {
+        let manifest = "aa  report.pdf\nbb  old report.pdf\ncc  notes.txt\n";
+        assert_eq!(
+            without_entry(manifest, "report.pdf"),
+            "bb  old report.pdf\ncc  notes.txt\n"
+        );
+        assert_eq!(without_entry("", "report.pdf"), "");
+
+        let mut manifest = String::new();
+        manifest_line(&mut manifest, "aa", "a\nbb  forged.txt");
+        manifest_line(&mut manifest, "cc", "notes.txt");
+        assert_eq!(manifest.lines().count(), 2);
+        assert_eq!(
+            without_entry(&manifest, "a\nbb  forged.txt"),
+            "cc  notes.txt\n"
+        );
+    }
+}
@@ -0,0 +1,716 @@
+mod extract;
+mod file_type;
+mod hook;
+mod inbox;
+mod ledger;
+mod limits;
+mod listing;
+mod manifest;
+mod raw_upload;
+mod session;
+mod text_pad;
+mod upload;
+
+use crate::archive::ExtractLimits;
+use crate::cli_args::Args;
+use crate::fs_object::{SizeFormatter, SizeUnits};
+use crate::style::STYLE_CSS;
+use askama::Template;
+use axum::{
+    body::Bytes,
+    extract::{ConnectInfo, DefaultBodyLimit, Multipart, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::{get, post, put},
+    Json, Router,
+};
+use extract::{extract_upload, ArchiveExtraction, ExtractedArchive};
+use file_type::FileTypeFilter;
+use hook::{move_file, UploadHook, UploadedFile};
+use inbox::{Inboxes, IndexEntry, INDEX_NAME};
+use ledger::{UploadLedger, UploadRecord};
+use limits::UploadLimits;
+use manifest::{Manifest, MANIFEST_NAME};
+use serde::{Deserialize, Serialize};
+use session::session_id;
+use std::{
+    fmt::Display,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio_stream::Stream;
+use tower_http::{limit::RequestBodyLimitLayer, trace::TraceLayer};
+use upload::{
+    content_length, is_temp_name, relative_folder, sanitize_filename, save_stream_to_file,
+    ExpectedDigest, SavedFile, UploadError,
+};
+
+static SCRIPT_JS: &[u8] = include_bytes!("../../templates/server_receiver_mode/script.js");
+static SHA256_JS: &[u8] = include_bytes!("../../templates/server_receiver_mode/sha256.js");
+const APP_TITLE: &str = concat!("Minicloud v", env!("CARGO_PKG_VERSION"));
+
+/// Form field carrying the hex SHA-256 of the file that follows it.
+const DIGEST_FIELD: &str = "sha256";
+
+/// Form field with the sender name or the picked inbox, sent before the files.
+const SENDER_FIELD: &str = "sender";
+
+/// Form field with the path of the file that follows it inside an uploaded folder,
+/// e.g. `photos/2024/beach.jpg`.
+const PATH_FIELD: &str = "path";
+
+#[derive(Clone)]
+pub struct ReceiverState {
+    pub uploads_path: Arc<PathBuf>,
+    pub max_file_size: usize,
+    pub manifest: Arc<Manifest>,
+    pub limits: Arc<UploadLimits>,
+    pub file_types: Arc<FileTypeFilter>,
+    pub hook: Option<Arc<UploadHook>>,
+    /// Files are saved to per-sender subdirectories if set.
+    pub inboxes: Option<Arc<Inboxes>>,
+    pub ledger: Arc<UploadLedger>,
+    /// How long uploaders may download and delete their own files, if at all.
+    pub uploader_access: Option<Duration>,
+    pub size_units: SizeUnits,
+    /// Received archives are extracted if set.
+    pub extraction: Option<Arc<ArchiveExtraction>>,
+}
+
+#[derive(Deserialize)]
+pub struct UploadParams {
+    /// Keep saving the remaining files after one fails.
+    #[serde(default)]
+    partial: bool,
+}
+
+/// Outcome of every file field of an upload form, in the order they were sent.
+#[derive(Default, Serialize)]
+pub struct UploadReport {
+    saved: usize,
+    failed: usize,
+    files: Vec<FieldReport>,
+    /// Why the form couldn't be read to the end, if it couldn't.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct FieldReport {
+    /// Name of the form field.
+    field: String,
+    /// File name as sent by the client, after sanitizing.
+    name: String,
+    #[serde(flatten)]
+    outcome: FieldOutcome,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum FieldOutcome {
+    Saved(ReceivedFile),
+    Failed {
+        error: String,
+        /// HTTP status code the error would have on its own.
+        code: u16,
+    },
+    /// Not processed because an earlier file failed and partial uploads weren't requested.
+    Skipped,
+}
+
+#[derive(Serialize)]
+pub struct ReceivedFile {
+    /// Path relative to the uploads directory.
+    path: String,
+    size: u64,
+    sha256: String,
+    /// Whether the content was checked against a checksum sent by the client.
+    verified: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extracted: Option<ExtractedArchive>,
+    /// Why the archive couldn't be extracted. The archive is kept then.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extract_error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "server_receiver_mode/index.html")]
+struct ReceiverTemplate<'a> {
+    title: &'a str,
+    max_size: usize,
+    /// Per-file limit in bytes, also checked by the page before uploading.
+    max_file_size: Option<u64>,
+    max_file_size_display: Option<String>,
+    /// Used and total quota.
+    quota: Option<(String, String)>,
+    /// Whether the sender has to give a name.
+    ask_sender: bool,
+    /// Inboxes to pick from instead of typing a name.
+    inbox_names: &'a [String],
+    max_text_size: usize,
+}
+
+pub fn setup(cli_args: &Args) -> Router {
+    let uploads_path = cli_args
+        .received_files_path
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("./uploads"));
+
+    if let Err(err) = std::fs::create_dir_all(&uploads_path) {
+        tracing::error!(
+            "Failed to create uploads directory {:?}: {err}",
+            uploads_path
+        );
+    }
+
+    tracing::info!(
+        "Receive mode enabled. Files will be saved to: {:?}",
+        uploads_path
+    );
+    tracing::info!(
+        "Maximum total files size per request is {} MiB",
+        cli_args.max_total_received_files_size
+    );
+
+    const MIB: u64 = 1024 * 1024;
+    let limits = UploadLimits::new(
+        uploads_path.clone(),
+        cli_args.max_file_size.map(|size| size.saturating_mul(MIB)),
+        cli_args.quota.map(|size| size.saturating_mul(MIB)),
+        cli_args.min_free_space.map(|size| size.saturating_mul(MIB)),
+        cli_args.size_units(),
+    );
+    let limits = Arc::new(limits);
+    if let Some(quota) = limits.quota {
+        tracing::info!(
+            "Uploads quota: {} of {} used",
+            SizeFormatter(limits.used(), limits.units),
+            SizeFormatter(quota, limits.units)
+        );
+    }
+
+    let file_types = FileTypeFilter::new(
+        &cli_args.allow_extensions,
+        &cli_args.deny_extensions,
+        &cli_args.allow_mime_types,
+        &cli_args.deny_mime_types,
+    );
+    if !file_types.is_empty() {
+        tracing::info!("Accepted file types: {file_types:?}");
+    }
+
+    let hook = cli_args.on_upload.clone().map(|command| {
+        let quarantine_dir = cli_args.quarantine_dir.clone();
+        if let Some(dir) = &quarantine_dir {
+            if let Err(err) = std::fs::create_dir_all(dir) {
+                tracing::error!("Failed to create quarantine directory {:?}: {err}", dir);
+            }
+            tracing::info!(
+                "Quarantine mode: files are staged in {:?} until `{command}` approves them",
+                dir
+            );
+        } else {
+            tracing::info!("Running `{command}` for every received file");
+        }
+        Arc::new(UploadHook {
+            command,
+            quarantine_dir,
+        })
+    });
+
+    let inboxes = (cli_args.ask_sender || !cli_args.inboxes.is_empty()).then(|| {
+        let inboxes = Inboxes::new(&uploads_path, &cli_args.inboxes, Arc::clone(&limits));
+        for name in &inboxes.names {
+            if let Err(err) = std::fs::create_dir_all(uploads_path.join(name)) {
+                tracing::error!("Failed to create inbox {name:?}: {err}");
+            }
+        }
+        if inboxes.names.is_empty() {
+            tracing::info!("Files are saved to a subdirectory named after the sender");
+        } else {
+            tracing::info!(
+                "Senders pick one of the inboxes: {}",
+                inboxes.names.join(", ")
+            );
+        }
+        Arc::new(inboxes)
+    });
+
+    let extraction = cli_args.extract_archives.then(|| {
+        tracing::info!(
+            "Received archives are extracted{}",
+            if cli_args.keep_archives {
+                " and kept"
+            } else {
+                ""
+            }
+        );
+        Arc::new(ArchiveExtraction {
+            keep_archives: cli_args.keep_archives,
+            limits: ExtractLimits {
+                max_size: cli_args.max_extracted_size.saturating_mul(MIB),
+                max_entries: cli_args.max_extracted_entries,
+            },
+        })
+    });
+
+    let state = ReceiverState {
+        manifest: Arc::new(Manifest::new(&uploads_path, Arc::clone(&limits))),
+        limits,
+        file_types: Arc::new(file_types),
+        hook,
+        inboxes,
+        ledger: Arc::new(UploadLedger::default()),
+        uploader_access: cli_args
+            .uploader_access
+            .map(|minutes| Duration::from_secs(minutes * 60)),
+        size_units: cli_args.size_units(),
+        extraction,
+        uploads_path: Arc::new(uploads_path),
+        max_file_size: cli_args.max_total_received_files_size,
+    };
+
+    Router::new()
+        .route("/", get(show_upload_form).post(accept_upload_form))
+        .route("/upload", post(raw_upload::post_file))
+        .route("/upload/{name}", put(raw_upload::put_file))
+        .route("/text", post(text_pad::accept_text))
+        .route("/uploads", get(listing::uploads_page))
+        .route("/uploads/file", get(listing::download_own_upload))
+        .route("/uploads/delete", post(listing::delete_own_upload))
+        .route("/script.js", get(serve_script_js))
+        .route("/sha256.js", get(serve_sha256_js))
+        .route("/style.css", get(serve_style_css))
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(
+            cli_args
+                .max_total_received_files_size
+                .saturating_mul(1024 * 1024),
+        ))
+        .layer(TraceLayer::new_for_http())
+        .with_state(state)
+}
+
+pub async fn show_upload_form(
+    State(state): State<ReceiverState>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    tracing::info!("Root page request");
+
+    let page = ReceiverTemplate {
+        title: APP_TITLE,
+        max_size: state.max_file_size,
+        max_file_size: state.limits.max_file_size,
+        max_file_size_display: state
+            .limits
+            .max_file_size
+            .map(|size| SizeFormatter(size, state.size_units).to_string()),
+        quota: state.limits.quota.map(|quota| {
+            (
+                SizeFormatter(state.limits.used(), state.size_units).to_string(),
+                SizeFormatter(quota, state.size_units).to_string(),
+            )
+        }),
+        ask_sender: state.inboxes.is_some(),
+        inbox_names: state
+            .inboxes
+            .as_ref()
+            .map_or(&[], |inboxes| inboxes.names.as_slice()),
+        max_text_size: text_pad::MAX_TEXT_SIZE,
+    }
+    .render()
+    .map_err(|err| {
+        tracing::error!("Template render error: {err}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(listing::with_session(session_id(&headers), Html(page)))
+}
+
+async fn serve_script_js() -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, "application/javascript"),
+            (header::CACHE_CONTROL, "public, max-age=3600"),
+        ],
+        SCRIPT_JS,
+    )
+}
+
+async fn serve_sha256_js() -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, "application/javascript"),
+            (header::CACHE_CONTROL, "public, max-age=3600"),
+        ],
+        SHA256_JS,
+    )
+}
+
+async fn serve_style_css() -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, "text/css; charset=utf-8"),
+            (header::CACHE_CONTROL, "public, max-age=3600"),
+        ],
+        STYLE_CSS,
+    )
+}
+
+/// Saves the files of the multipart form and reports the outcome of each as JSON.
+///
+/// A text field named `sha256` sets the expected checksum of the file that follows it,
+/// and a `path` field its folder, which is created in the uploads directory.
+/// With inboxes enabled, a `sender` field must come before the files.
+/// API clients can instead put a `Content-Digest` or `Digest` header in the headers of the
+/// file's own part. Request headers are not used, their digest would cover the whole form.
+///
+/// The first failed file stops the upload and the files after it are skipped, unless
+/// `?partial=true` is given. The response is `200 OK` if every file was saved, `207 Multi-Status`
+/// if only some were, and the status of the first error otherwise.
+pub async fn accept_upload_form(
+    State(state): State<ReceiverState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Query(params): Query<UploadParams>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> (StatusCode, Json<UploadReport>) {
+    let mut report = UploadReport::default();
+    let mut first_error = None;
+    let mut expected_digest = None;
+    let mut folder = None;
+    let mut inbox = None;
+
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(err) => {
+                tracing::warn!("Failed to read multipart field: {err}");
+                let error = UploadError::BadRequest("Malformed upload form".into());
+                report.error = Some(error.to_string());
+                first_error.get_or_insert(error);
+                break;
+            }
+        };
+
+        if field.file_name().is_none() {
+            if field.name() == Some(DIGEST_FIELD) {
+                expected_digest = Some(match field.text().await {
+                    Ok(hex) => ExpectedDigest::from_hex(&hex).ok_or_else(|| {
+                        UploadError::BadRequest(format!("Malformed SHA-256 checksum: {hex}"))
+                    }),
+                    Err(err) => {
+                        tracing::warn!("Failed to read checksum field: {err}");
+                        Err(UploadError::BadRequest("Malformed upload form".into()))
+                    }
+                });
+            } else if field.name() == Some(PATH_FIELD) {
+                folder = Some(match field.text().await {
+                    Ok(path) => relative_folder(&path),
+                    Err(err) => {
+                        tracing::warn!("Failed to read path field: {err}");
+                        Err(UploadError::BadRequest("Malformed upload form".into()))
+                    }
+                });
+            } else if let (Some(SENDER_FIELD), Some(inboxes)) = (field.name(), &state.inboxes) {
+                inbox = Some(match field.text().await {
+                    Ok(sender) => inboxes.inbox_for(&sender),
+                    Err(err) => {
+                        tracing::warn!("Failed to read sender field: {err}");
+                        Err(UploadError::BadRequest("Malformed upload form".into()))
+                    }
+                });
+            }
+            continue;
+        }
+
+        let field_name = field.name().unwrap_or_default().to_owned();
+        let safe_name = sanitize_filename(field.file_name()).to_owned();
+        let form_digest = expected_digest.take();
+        let folder = folder.take().transpose().map(Option::flatten);
+        let expected = match ExpectedDigest::from_headers(field.headers()) {
+            Ok(None) => form_digest,
+            header_digest => header_digest.transpose(),
+        };
+
+        let outcome = if first_error.is_some() && !params.partial {
+            FieldOutcome::Skipped
+        } else {
+            let received = match (expected.transpose(), inbox.clone().transpose(), folder) {
+                (Ok(expected), Ok(inbox), Ok(folder)) => {
+                    let upload = IncomingFile {
+                        file_name: &safe_name,
+                        inbox: inbox.as_deref(),
+                        folder: folder.as_deref(),
+                        size_hint: content_length(field.headers()),
+                        expected,
+                        client_ip: client.ip(),
+                        session: session_id(&headers),
+                    };
+                    receive_file(&state, field, upload).await
+                }
+                (Err(err), _, _) | (_, Err(err), _) | (_, _, Err(err)) => Err(err),
+            };
+            match received {
+                Ok(received) => {
+                    report.saved += 1;
+                    FieldOutcome::Saved(received)
+                }
+                Err(err) => {
+                    tracing::warn!("Upload of {safe_name} from {} failed: {err}", client.ip());
+                    report.failed += 1;
+                    let outcome = FieldOutcome::Failed {
+                        error: err.to_string(),
+                        code: err.status().as_u16(),
+                    };
+                    first_error.get_or_insert(err);
+                    outcome
+                }
+            }
+        };
+
+        report.files.push(FieldReport {
+            field: field_name,
+            name: safe_name,
+            outcome,
+        });
+    }
+
+    let status = match first_error {
+        None => StatusCode::OK,
+        Some(_) if report.saved > 0 => StatusCode::MULTI_STATUS,
+        Some(err) => err.status(),
+    };
+    (status, Json(report))
+}
+
+/// A file sent by the client and what came with it.
+struct IncomingFile<'a> {
+    file_name: &'a str,
+    inbox: Option<&'a str>,
+    /// Folder inside the inbox or the uploads directory.
+    folder: Option<&'a str>,
+    /// Size announced by the client before the content.
+    size_hint: Option<u64>,
+    expected: Option<ExpectedDigest>,
+    client_ip: IpAddr,
+    session: Option<String>,
+}
+
+/// Saves a file streamed from an upload form field or a raw request body.
+async fn receive_file<S, E>(
+    state: &ReceiverState,
+    stream: S,
+    upload: IncomingFile<'_>,
+) -> Result<ReceivedFile, UploadError>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+    E: Display,
+{
+    state.file_types.check_name(upload.file_name)?;
+    let name = upload_name(state, upload.inbox, upload.folder, upload.file_name).await?;
+    let staged_path = staging_dir(state).join(&name);
+    let verified = upload.expected.is_some();
+
+    let saved = save_stream_to_file(
+        &staged_path,
+        stream,
+        upload.size_hint,
+        upload.expected,
+        &state.limits,
+        &state.file_types,
+    )
+    .await?;
+
+    // Archives are extracted before the hook runs, so it never gets an archive that is
+    // removed after extraction
+    let extraction = extract_upload(
+        state,
+        &name,
+        &staged_path,
+        upload.client_ip,
+        upload.session.as_deref(),
+    )
+    .await;
+    let (extracted, extract_error) = match extraction {
+        Some(Ok(extracted)) => (Some(extracted), None),
+        Some(Err(err)) => (None, Some(err)),
+        None => (None, None),
+    };
+
+    if extracted
+        .as_ref()
+        .is_none_or(|extracted| extracted.archive_kept)
+    {
+        let file_path =
+            complete_upload(state, &name, &staged_path, &saved, upload.client_ip).await?;
+        register_upload(state, &name, &saved, upload.client_ip, upload.session).await;
+
+        tracing::info!(
+            "Received file from {}: {} (SHA-256 {}{})",
+            upload.client_ip,
+            file_path.display(),
+            saved.sha256,
+            if verified { ", verified" } else { "" }
+        );
+    }
+
+    Ok(ReceivedFile {
+        path: name,
+        size: saved.size,
+        sha256: saved.sha256,
+        verified,
+        extracted,
+        extract_error,
+    })
+}
+
+/// Returns the path of a received file relative to the uploads directory, creating
+/// the sender's inbox and the file's folder if needed. `inbox` is required when inboxes
+/// are enabled. `folder` must have been checked with [`relative_folder`].
+async fn upload_name(
+    state: &ReceiverState,
+    inbox: Option<&str>,
+    folder: Option<&str>,
+    file_name: &str,
+) -> Result<String, UploadError> {
+    let dir = match (&state.inboxes, inbox, folder) {
+        (None, _, folder) => folder.map(str::to_owned),
+        (Some(_), Some(inbox), None) => Some(inbox.to_owned()),
+        (Some(_), Some(inbox), Some(folder)) => Some(format!("{inbox}/{folder}")),
+        (Some(_), None, _) => {
+            return Err(UploadError::BadRequest(
+                "The sender name must be sent before the files".into(),
+            ))
+        }
+    };
+    let name = match &dir {
+        Some(dir) => format!("{dir}/{file_name}"),
+        None => file_name.to_owned(),
+    };
+    // Temp names are hidden from the listing, where the uploader couldn't manage the file
+    if name == MANIFEST_NAME || name == INDEX_NAME || is_temp_name(file_name) {
+        return Err(UploadError::BadRequest(format!(
+            "{name} is a reserved file name"
+        )));
+    }
+
+    if let Some(dir) = dir {
+        for parent in [staging_dir(state), state.uploads_path.as_path()] {
+            tokio::fs::create_dir_all(parent.join(&dir))
+                .await
+                .map_err(|err| {
+                    tracing::error!("Failed to create directory {dir:?}: {err}");
+                    UploadError::Internal
+                })?;
+        }
+    }
+
+    Ok(name)
+}
+
+/// Directory files are written to before [`complete_upload`].
+fn staging_dir(state: &ReceiverState) -> &Path {
+    state
+        .hook
+        .as_ref()
+        .and_then(|hook| hook.quarantine_dir.as_deref())
+        .unwrap_or(&state.uploads_path)
+}
+
+/// Adds an accepted file to the inbox index and remembers who uploaded it.
+async fn register_upload(
+    state: &ReceiverState,
+    name: &str,
+    saved: &SavedFile,
+    client_ip: IpAddr,
+    session: Option<String>,
+) {
+    if let (Some(inboxes), Some((inbox, file_name))) = (&state.inboxes, name.split_once('/')) {
+        let entry = IndexEntry {
+            inbox,
+            name: file_name,
+            size: saved.size,
+            sha256: &saved.sha256,
+            client_ip,
+        };
+        if let Err(err) = inboxes.record(&entry).await {
+            tracing::error!("Failed to add {name} to {INDEX_NAME}: {err}");
+        }
+    }
+
+    state.ledger.record(
+        name,
+        UploadRecord {
+            uploader: client_ip,
+            session,
+            time: SystemTime::now(),
+        },
+    );
+}
+
+/// Runs the upload hook for a saved file and records its checksum.
+///
+/// In quarantine mode the file is moved from the staging directory into the uploads
+/// directory only if the hook approves it; otherwise the hook runs in the background.
+async fn complete_upload(
+    state: &ReceiverState,
+    name: &str,
+    staged_path: &Path,
+    saved: &SavedFile,
+    client_ip: IpAddr,
+) -> Result<PathBuf, UploadError> {
+    let file_path = state.uploads_path.join(name);
+
+    if let Some(hook) = &state.hook {
+        let uploaded = UploadedFile {
+            path: staged_path,
+            name,
+            size: saved.size,
+            sha256: &saved.sha256,
+            client_ip,
+        };
+
+        if hook.quarantine_dir.is_some() {
+            if !hook.approves(&uploaded).await {
+                let _ = tokio::fs::remove_file(staged_path).await;
+                state.limits.release(saved.size);
+                return Err(UploadError::Rejected {
+                    name: name.to_owned(),
+                });
+            }
+
+            let overwritten = tokio::fs::metadata(&file_path)
+                .await
+                .map_or(0, |metadata| metadata.len());
+            move_file(staged_path, &file_path).await.map_err(|err| {
+                tracing::error!("Failed to move {name} out of quarantine: {err}");
+                UploadError::Internal
+            })?;
+            state.limits.release(overwritten);
+        } else {
+            let hook = Arc::clone(hook);
+            let (path, name, sha256) = (file_path.clone(), name.to_owned(), saved.sha256.clone());
+            let size = saved.size;
+            tokio::spawn(async move {
+                let uploaded = UploadedFile {
+                    path: &path,
+                    name: &name,
+                    size,
+                    sha256: &sha256,
+                    client_ip,
+                };
+                hook.approves(&uploaded).await;
+            });
+        }
+    }
+
+    if let Err(err) = state.manifest.record(name, &saved.sha256).await {
+        tracing::error!("Failed to record checksum of {name}: {err}");
+    }
+
+    Ok(file_path)
+}
@@ -0,0 +1,112 @@
+//! Uploads of a single file as the raw request body, for curl and scripts:
+//! `curl -T report.pdf http://host:48666/upload/`.
+
+use super::{
+    receive_file,
+    session::session_id,
+    upload::{content_length, sanitize_filename, ExpectedDigest, UploadError},
+    IncomingFile, ReceiverState,
+};
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+};
+use serde::Deserialize;
+use std::net::SocketAddr;
+
+#[derive(Deserialize)]
+pub struct RawUploadParams {
+    /// File name, for `POST /upload?name=`.
+    name: Option<String>,
+    /// Sender name or inbox, required when inboxes are enabled.
+    sender: Option<String>,
+    /// Expected hex SHA-256, an alternative to the `Content-Digest` and `Digest` headers.
+    sha256: Option<String>,
+}
+
+/// `PUT /upload/{name}`
+pub async fn put_file(
+    State(state): State<ReceiverState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(name): Path<String>,
+    Query(params): Query<RawUploadParams>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<(StatusCode, String), UploadError> {
+    receive_body(&state, client, &name, &params, &headers, body).await
+}
+
+/// `POST /upload?name=`
+pub async fn post_file(
+    State(state): State<ReceiverState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Query(params): Query<RawUploadParams>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<(StatusCode, String), UploadError> {
+    let name = params.name.clone().unwrap_or_default();
+    receive_body(&state, client, &name, &params, &headers, body).await
+}
+
+/// Saves the request body and responds with the stored path, relative to the uploads
+/// directory, on its own line. An extracted archive is followed by its directory with a
+/// trailing `/`, and the archive line is left out if the archive was deleted.
+async fn receive_body(
+    state: &ReceiverState,
+    client: SocketAddr,
+    raw_name: &str,
+    params: &RawUploadParams,
+    headers: &HeaderMap,
+    body: Body,
+) -> Result<(StatusCode, String), UploadError> {
+    if raw_name.trim().is_empty() {
+        return Err(UploadError::BadRequest("The file name is missing".into()));
+    }
+    let file_name = sanitize_filename(Some(raw_name));
+
+    // Reject what is known to be too large before reading the body
+    let content_length = content_length(headers);
+    if let (Some(length), Some(limit)) = (content_length, state.limits.max_file_size) {
+        if length > limit {
+            return Err(UploadError::TooLarge {
+                name: file_name.to_owned(),
+                limit,
+                units: state.size_units,
+            });
+        }
+    }
+
+    let expected = match (ExpectedDigest::from_headers(headers)?, &params.sha256) {
+        (Some(digest), _) => Some(digest),
+        (None, Some(hex)) => Some(ExpectedDigest::from_hex(hex).ok_or_else(|| {
+            UploadError::BadRequest(format!("Malformed SHA-256 checksum: {hex}"))
+        })?),
+        (None, None) => None,
+    };
+
+    let inbox = match (&state.inboxes, &params.sender) {
+        (Some(inboxes), Some(sender)) => Some(inboxes.inbox_for(sender)?),
+        _ => None,
+    };
+
+    let upload = IncomingFile {
+        file_name,
+        inbox: inbox.as_deref(),
+        folder: None,
+        size_hint: content_length,
+        expected,
+        client_ip: client.ip(),
+        session: session_id(headers),
+    };
+    let received = receive_file(state, body.into_data_stream(), upload).await?;
+
+    let response = match &received.extracted {
+        Some(extracted) if extracted.archive_kept => {
+            format!("{}\n{}/\n", received.path, extracted.dir)
+        }
+        Some(extracted) => format!("{}/\n", extracted.dir),
+        None => format!("{}\n", received.path),
+    };
+    Ok((StatusCode::CREATED, response))
+}
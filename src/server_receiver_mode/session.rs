@@ -0,0 +1,64 @@
+//! Anonymous browser sessions that let uploaders manage their own files.
+
+use crate::checksum::to_hex;
+use axum::http::{header, HeaderMap, HeaderValue};
+
+pub const SESSION_COOKIE: &str = "minicloud_session";
+
+/// Returns the session ID from the request cookies.
+pub fn session_id(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|cookie| {
+            let (name, value) = cookie.trim().split_once('=')?;
+            (name == SESSION_COOKIE && is_valid_id(value)).then(|| value.to_owned())
+        })
+}
+
+fn is_valid_id(id: &str) -> bool {
+    id.len() == 32 && id.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+/// Generates a new random session ID.
+pub fn new_session_id() -> String {
+    let mut bytes = [0; 16];
+    getrandom::fill(&mut bytes).expect("the OS random number generator is unavailable");
+    to_hex(&bytes)
+}
+
+/// `Set-Cookie` value for the session. The cookie lives until the browser is closed.
+pub fn session_cookie(id: &str) -> HeaderValue {
+    HeaderValue::from_str(&format!(
+        "{SESSION_COOKIE}={id}; Path=/; HttpOnly; SameSite=Strict"
+    ))
+    .expect("session ID is hex")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_id() {
+        let id = new_session_id();
+        assert!(is_valid_id(&id));
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(session_id(&headers), None);
+
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_str(&format!("theme=dark; {SESSION_COOKIE}={id}")).unwrap(),
+        );
+        assert_eq!(session_id(&headers), Some(id));
+
+        headers.insert(
+            header::COOKIE,
+            HeaderValue::from_static("minicloud_session=../../etc"),
+        );
+        assert_eq!(session_id(&headers), None);
+    }
+}
@@ -0,0 +1,141 @@
+//! Text pad for sending a URL or a snippet instead of a file. Posted text is saved as
+//! a timestamped `.txt` file and printed to the server console.
+
+use super::{
+    complete_upload, listing::file_timestamp, register_upload, session::session_id, staging_dir,
+    upload::save_bytes_to_file, upload_name, ReceiverState, UploadError,
+};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::HeaderMap,
+    Form,
+};
+use serde::Deserialize;
+use std::{
+    io,
+    net::SocketAddr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Longest text accepted from the pad, in bytes.
+pub const MAX_TEXT_SIZE: usize = 1024 * 1024;
+
+#[derive(Deserialize)]
+pub struct TextForm {
+    text: String,
+    /// Sender name or inbox, required when inboxes are enabled.
+    sender: Option<String>,
+}
+
+pub async fn accept_text(
+    State(state): State<ReceiverState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Form(form): Form<TextForm>,
+) -> Result<String, UploadError> {
+    if form.text.trim().is_empty() {
+        return Err(UploadError::BadRequest("The text is empty".into()));
+    }
+    if form.text.len() > MAX_TEXT_SIZE {
+        return Err(UploadError::TooLarge {
+            name: "The text".into(),
+            limit: MAX_TEXT_SIZE as u64,
+            units: state.size_units,
+        });
+    }
+
+    let inbox = match (&state.inboxes, &form.sender) {
+        (Some(inboxes), Some(sender)) => Some(inboxes.inbox_for(sender)?),
+        _ => None,
+    };
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let base_name = format!("text-{}", file_timestamp(now));
+    state.file_types.check_name(&format!("{base_name}.txt"))?;
+    state
+        .file_types
+        .check_content(&format!("{base_name}.txt"), form.text.as_bytes())?;
+
+    // Texts sent within the same second get a counter. The name is reserved by creating
+    // an empty file, so that concurrent texts can't pick the same one
+    let mut suffix = 1;
+    let (file_name, name) = loop {
+        let file_name = match suffix {
+            1 => format!("{base_name}.txt"),
+            _ => format!("{base_name}-{suffix}.txt"),
+        };
+        let name = upload_name(&state, inbox.as_deref(), None, &file_name).await?;
+        let reserved = tokio::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(state.uploads_path.join(&name))
+            .await;
+        match reserved {
+            Ok(_) => break (file_name, name),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => suffix += 1,
+            Err(err) => {
+                tracing::error!("Failed to create {name}: {err}");
+                return Err(UploadError::Internal);
+            }
+        }
+    };
+
+    let staged_path = staging_dir(&state).join(&name);
+    let saved = async {
+        let saved = save_bytes_to_file(&staged_path, form.text.as_bytes(), &state.limits).await?;
+        complete_upload(&state, &name, &staged_path, &saved, client.ip()).await?;
+        Ok(saved)
+    }
+    .await;
+    let saved = match saved {
+        Ok(saved) => saved,
+        Err(err) => {
+            // The text never replaced the reserved file
+            let _ = tokio::fs::remove_file(state.uploads_path.join(&name)).await;
+            return Err(err);
+        }
+    };
+    register_upload(&state, &name, &saved, client.ip(), session_id(&headers)).await;
+
+    tracing::info!(
+        "Received text from {}: {}",
+        client.ip(),
+        state.uploads_path.join(&name).display()
+    );
+    println!("----- Text from {} -----", client.ip());
+    println!("{}", terminal_safe(form.text.trim_end()));
+    println!("-----");
+
+    Ok(format!("Text saved as {file_name}"))
+}
+
+/// Escapes control characters other than line breaks and tabs, so that a text can't send
+/// escape sequences to the server terminal.
+fn terminal_safe(text: &str) -> String {
+    let mut safe = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_control() && c != '\n' && c != '\t' {
+            safe.extend(c.escape_default());
+        } else {
+            safe.push(c);
+        }
+    }
+    safe
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terminal_safe() {
+        assert_eq!(terminal_safe("a\tb\nc"), "a\tb\nc");
+        assert_eq!(
+            terminal_safe("\x1b[2J\x1b]0;title\x07\r"),
+            "\\u{1b}[2J\\u{1b}]0;title\\u{7}\\r"
+        );
+        assert_eq!(terminal_safe("café"), "café");
+    }
+}
@@ -0,0 +1,490 @@
+//! Writing received files to disk and verifying their integrity.
+
+use super::file_type::FileTypeFilter;
+use super::inbox::INDEX_NAME;
+use super::limits::UploadLimits;
+use super::manifest::MANIFEST_NAME;
+use crate::checksum::to_hex;
+use crate::fs_object::{SizeFormatter, SizeUnits};
+use axum::{
+    body::Bytes,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    pin::pin,
+};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio_stream::{Stream, StreamExt};
+
+/// Reasons an upload is rejected, reported to the client as plain text or in an
+/// [`UploadReport`](super::UploadReport).
+#[derive(Clone, Debug)]
+pub enum UploadError {
+    BadRequest(String),
+    /// The received file doesn't match the checksum sent by the client.
+    ChecksumMismatch {
+        name: String,
+    },
+    /// The file is larger than the per-file limit.
+    TooLarge {
+        name: String,
+        limit: u64,
+        units: SizeUnits,
+    },
+    /// The uploads directory would grow beyond the quota.
+    QuotaExceeded {
+        limit: u64,
+        units: SizeUnits,
+    },
+    /// The disk would have less than the minimum free space left.
+    DiskFull,
+    /// The upload hook didn't approve the file in quarantine mode.
+    Rejected {
+        name: String,
+    },
+    /// The extension or the content type of the file is not accepted.
+    UnsupportedType {
+        name: String,
+        reason: String,
+    },
+    Internal,
+}
+
+impl UploadError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            UploadError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            UploadError::ChecksumMismatch { .. } | UploadError::Rejected { .. } => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            UploadError::TooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            UploadError::QuotaExceeded { .. } | UploadError::DiskFull => {
+                StatusCode::INSUFFICIENT_STORAGE
+            }
+            UploadError::UnsupportedType { .. } => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            UploadError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UploadError::BadRequest(message) => f.write_str(message),
+            UploadError::ChecksumMismatch { name } => {
+                write!(f, "Checksum mismatch for {name}, the file was discarded")
+            }
+            UploadError::TooLarge { name, limit, units } => write!(
+                f,
+                "{name} is larger than the maximum file size of {}",
+                SizeFormatter(*limit, *units)
+            ),
+            UploadError::QuotaExceeded { limit, units } => write!(
+                f,
+                "The upload quota of {} is used up",
+                SizeFormatter(*limit, *units)
+            ),
+            UploadError::DiskFull => f.write_str("Not enough free disk space on the server"),
+            UploadError::Rejected { name } => {
+                write!(f, "{name} was rejected by the server's upload check")
+            }
+            UploadError::UnsupportedType { name, reason } => {
+                write!(f, "{name} was rejected: {reason}")
+            }
+            UploadError::Internal => f.write_str("Failed to save the file"),
+        }
+    }
+}
+
+impl IntoResponse for UploadError {
+    fn into_response(self) -> Response {
+        (self.status(), self.to_string()).into_response()
+    }
+}
+
+/// A file written by [`save_stream_to_file`].
+pub struct SavedFile {
+    pub size: u64,
+    /// Hex SHA-256 of the content.
+    pub sha256: String,
+}
+
+/// SHA-256 digest the client expects the received file to have.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExpectedDigest(pub [u8; 32]);
+
+impl ExpectedDigest {
+    /// Parses a hex digest, as sent by the upload page in a `sha256` form field.
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.trim();
+        // `from_str_radix` alone would also accept a sign, e.g. `+f`
+        if hex.len() != 64 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let mut digest = [0; 32];
+        for (byte, pair) in digest.iter_mut().zip(hex.as_bytes().chunks(2)) {
+            *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+        }
+        Some(Self(digest))
+    }
+
+    /// Finds a SHA-256 digest in the `Content-Digest` (RFC 9530) or `Digest` (RFC 3230) header.
+    ///
+    /// Other algorithms are ignored. `Err` means a SHA-256 digest is present but malformed.
+    pub fn from_headers(headers: &HeaderMap) -> Result<Option<Self>, UploadError> {
+        // Content-Digest: sha-256=:<base64>:, sha-512=:<base64>:
+        let content_digest = headers
+            .get_all("content-digest")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .find_map(|member| {
+                let (algo, value) = member.split_once('=')?;
+                algo.trim()
+                    .eq_ignore_ascii_case("sha-256")
+                    .then(|| value.trim().trim_matches(':'))
+            });
+
+        // Digest: SHA-256=<base64>,MD5=<base64>
+        let digest = || {
+            headers
+                .get_all("digest")
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .find_map(|member| {
+                    let (algo, value) = member.split_once('=')?;
+                    algo.trim()
+                        .eq_ignore_ascii_case("sha-256")
+                        .then(|| value.trim())
+                })
+        };
+
+        let Some(encoded) = content_digest.or_else(digest) else {
+            return Ok(None);
+        };
+
+        BASE64
+            .decode(encoded)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(|digest| Some(Self(digest)))
+            .ok_or_else(|| UploadError::BadRequest("Malformed SHA-256 digest header".into()))
+    }
+}
+
+/// Streams the body of a form field or a request into a temporary file and moves it to
+/// `file_path` once complete.
+///
+/// The content type is checked against the `filter` on the first chunk, before anything
+/// is written to disk, and so is the free space for the `size_hint` the client announced.
+/// When `expected` is given, the file is only moved into place if its SHA-256 matches.
+/// Every chunk is checked against the `limits` before it is written.
+pub async fn save_stream_to_file<S, E>(
+    file_path: &Path,
+    stream: S,
+    size_hint: Option<u64>,
+    expected: Option<ExpectedDigest>,
+    limits: &UploadLimits,
+    filter: &FileTypeFilter,
+) -> Result<SavedFile, UploadError>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+    E: fmt::Display,
+{
+    let mut stream = pin!(stream);
+    let name = file_name(file_path);
+    let read_error = |err: E| {
+        tracing::error!("Failed to read chunk: {err}");
+        UploadError::BadRequest("Failed to read the uploaded file".into())
+    };
+
+    let mut next_chunk = stream.next().await.transpose().map_err(read_error)?;
+    filter.check_content(name, next_chunk.as_deref().unwrap_or_default())?;
+
+    let tmp_path = temp_path(file_path);
+    let mut budget = limits.file_budget(name);
+
+    limits.check_free_space(size_hint.unwrap_or(0))?;
+
+    let file = tokio::fs::File::create(&tmp_path).await.map_err(|err| {
+        tracing::error!("Failed to create temp file {}: {err}", tmp_path.display());
+        UploadError::Internal
+    })?;
+
+    let mut writer = BufWriter::new(file);
+    let mut hasher = Sha256::new();
+    let mut size = 0;
+
+    while let Some(chunk) = next_chunk {
+        if let Err(err) = budget.add(chunk.len() as u64) {
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            return Err(err);
+        }
+
+        hasher.update(&chunk);
+        size += chunk.len() as u64;
+        if let Err(err) = writer.write_all(&chunk).await {
+            tracing::error!("Failed to write chunk to file: {err}");
+            let _ = tokio::fs::remove_file(&tmp_path).await;
+            return Err(UploadError::Internal);
+        }
+
+        next_chunk = stream.next().await.transpose().map_err(|err| {
+            let _ = std::fs::remove_file(&tmp_path); // Видаляємо тимчасовий файл при помилці
+            read_error(err)
+        })?;
+    }
+
+    if let Err(err) = writer.flush().await {
+        tracing::error!("Failed to flush file to disk: {err}");
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+        return Err(UploadError::Internal);
+    }
+
+    let digest: [u8; 32] = hasher.finalize().into();
+    if expected.is_some_and(|expected| expected.0 != digest) {
+        tracing::warn!("Checksum mismatch, discarding {}", file_path.display());
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+        return Err(UploadError::ChecksumMismatch {
+            name: file_name(file_path).to_owned(),
+        });
+    }
+
+    let overwritten = tokio::fs::metadata(file_path)
+        .await
+        .map_or(0, |metadata| metadata.len());
+    tokio::fs::rename(&tmp_path, file_path)
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to rename temp file to final destination: {err}");
+            let _ = std::fs::remove_file(&tmp_path);
+            UploadError::Internal
+        })?;
+    // The overwritten file no longer counts towards the quota
+    limits.release(overwritten);
+
+    budget.commit();
+
+    Ok(SavedFile {
+        size,
+        sha256: to_hex(&digest),
+    })
+}
+
+/// Saves content received in full, such as posted text, within the `limits`.
+pub async fn save_bytes_to_file(
+    file_path: &Path,
+    content: &[u8],
+    limits: &UploadLimits,
+) -> Result<SavedFile, UploadError> {
+    let mut budget = limits.file_budget(file_name(file_path));
+    budget.add(content.len() as u64)?;
+    limits.check_free_space(content.len() as u64)?;
+
+    let tmp_path = temp_path(file_path);
+    let written = match tokio::fs::write(&tmp_path, content).await {
+        Ok(()) => tokio::fs::rename(&tmp_path, file_path).await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = written {
+        tracing::error!("Failed to save {}: {err}", file_path.display());
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+        return Err(UploadError::Internal);
+    }
+
+    budget.commit();
+
+    Ok(SavedFile {
+        size: content.len() as u64,
+        sha256: to_hex(&Sha256::digest(content)),
+    })
+}
+
+/// Size of a request body or multipart part from its `Content-Length` header.
+pub fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+}
+
+/// Folders of an uploaded folder deeper than this are rejected, so that their files stay
+/// within the depth of the uploads listing.
+pub const MAX_FOLDER_DEPTH: usize = 6;
+
+/// Returns the folder of a file's path inside an uploaded folder, e.g. `photos/2024` for
+/// `photos/2024/beach.jpg`, or `None` for a bare file name. The file name itself is taken
+/// from the file part. Every folder must be a plain name that can't leave the uploads
+/// directory.
+pub fn relative_folder(path: &str) -> Result<Option<String>, UploadError> {
+    let mut components: Vec<&str> = path
+        .split(['/', '\\'])
+        .filter(|component| !component.is_empty())
+        .collect();
+    components.pop();
+    if components.is_empty() {
+        return Ok(None);
+    }
+
+    let is_valid = |component: &str| {
+        sanitize_filename(Some(component)) == component
+            && !component.chars().any(char::is_control)
+            && !is_temp_name(component)
+            && component != MANIFEST_NAME
+            && component != INDEX_NAME
+    };
+    if components.len() > MAX_FOLDER_DEPTH || !components.iter().all(|c| is_valid(c)) {
+        return Err(UploadError::BadRequest(format!(
+            "{path} can't be used as a folder path"
+        )));
+    }
+
+    Ok(Some(components.join("/")))
+}
+
+/// Returns a unique hidden path next to `path`, for writing the file before renaming
+/// it into place, e.g. `.report.pdf.1f3a9c0e5b7d2a64.part`.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let mut bytes = [0; 8];
+    getrandom::fill(&mut bytes).expect("the OS random number generator is unavailable");
+    path.with_file_name(format!(".{}.{}.part", file_name(path), to_hex(&bytes)))
+}
+
+/// Checks whether the file name was made by [`temp_path`].
+pub fn is_temp_name(file_name: &str) -> bool {
+    file_name.starts_with('.') && file_name.ends_with(".part")
+}
+
+pub fn file_name(path: &Path) -> &str {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("unnamed")
+}
+
+pub fn sanitize_filename(raw_name: Option<&str>) -> &str {
+    raw_name
+        .and_then(|name| name.rsplit('\\').next())
+        .and_then(|name| Path::new(name).file_name())
+        .and_then(|name| name.to_str())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("unnamed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename(Some("image.png")), "image.png");
+        assert_eq!(sanitize_filename(Some("../../etc/passwd")), "passwd");
+        assert_eq!(
+            sanitize_filename(Some(r"C:\Windows\system32\cmd.exe")),
+            "cmd.exe"
+        );
+        assert_eq!(sanitize_filename(Some("")), "unnamed");
+        assert_eq!(sanitize_filename(None), "unnamed");
+    }
+
+    #[test]
+    fn test_relative_folder() {
+        assert_eq!(relative_folder("beach.jpg").unwrap(), None);
+        assert_eq!(
+            relative_folder("photos/2024/beach.jpg").unwrap().as_deref(),
+            Some("photos/2024")
+        );
+        assert_eq!(
+            relative_folder(r"photos\\beach.jpg").unwrap().as_deref(),
+            Some("photos")
+        );
+        assert!(relative_folder("../etc/passwd").is_err());
+        assert!(relative_folder("photos/./beach.jpg").is_err());
+        assert!(relative_folder("SHA256SUMS/beach.jpg").is_err());
+        assert!(relative_folder(&"a/".repeat(MAX_FOLDER_DEPTH + 1)).is_ok());
+        assert!(relative_folder(&"a/".repeat(MAX_FOLDER_DEPTH + 2)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_save_stream_size_hint() {
+        let dir = TempDir::new();
+        let limits = UploadLimits::new(
+            dir.path().to_owned(),
+            None,
+            None,
+            Some(1),
+            SizeUnits::Binary,
+        );
+        let filter = FileTypeFilter::default();
+        let chunks = || {
+            tokio_stream::iter([Ok::<_, std::convert::Infallible>(Bytes::from_static(
+                b"hello",
+            ))])
+        };
+
+        let path = dir.join("huge.txt");
+        let result =
+            save_stream_to_file(&path, chunks(), Some(u64::MAX / 2), None, &limits, &filter).await;
+        assert!(matches!(result, Err(UploadError::DiskFull)));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        let path = dir.join("small.txt");
+        let saved = save_stream_to_file(&path, chunks(), Some(5), None, &limits, &filter)
+            .await
+            .unwrap();
+        assert_eq!(saved.size, 5);
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn test_temp_path() {
+        let temp = temp_path(Path::new("uploads/a.txt"));
+        assert_eq!(temp.parent(), Some(Path::new("uploads")));
+        assert!(is_temp_name(file_name(&temp)));
+        assert_ne!(temp, temp_path(Path::new("uploads/a.txt")));
+        assert!(!is_temp_name("a.txt"));
+    }
+
+    #[test]
+    fn test_expected_digest() {
+        // SHA-256 of "abc"
+        let hex = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let base64 = "ungWv48Bz+pBQUDeXa4iI7ADYaOWF3qctBD/YfIAFa0=";
+        let expected = ExpectedDigest::from_hex(hex).unwrap();
+        assert_eq!(ExpectedDigest::from_hex("abc"), None);
+        assert_eq!(ExpectedDigest::from_hex(&"+f".repeat(32)), None);
+
+        let mut headers = HeaderMap::new();
+        assert!(ExpectedDigest::from_headers(&headers).unwrap().is_none());
+
+        headers.insert(
+            "content-digest",
+            HeaderValue::from_str(&format!("sha-512=:AAAA:, sha-256=:{base64}:")).unwrap(),
+        );
+        assert_eq!(
+            ExpectedDigest::from_headers(&headers).unwrap(),
+            Some(expected)
+        );
+
+        headers.clear();
+        headers.insert(
+            "digest",
+            HeaderValue::from_str(&format!("MD5=AAAA,SHA-256={base64}")).unwrap(),
+        );
+        assert_eq!(
+            ExpectedDigest::from_headers(&headers).unwrap(),
+            Some(expected)
+        );
+
+        headers.insert("digest", HeaderValue::from_static("SHA-256=broken"));
+        assert!(ExpectedDigest::from_headers(&headers).is_err());
+    }
+}
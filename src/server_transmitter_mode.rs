@@ -1,19 +1,27 @@
+use crate::archive::{self, ArchiveEntry, ArchiveFormat};
+use crate::checksum::{self, HashAlgo};
 use crate::cli_args::Args;
-use crate::fs_object::{FsObject, FsSummary};
-use crate::html_page_utils::unordered_list;
-use crate::storage::content_recursively;
+use crate::content_search::{stream_matches, GrepLimits};
+use crate::fs_object::{FsObject, FsObjects, FsSummary, SizeUnits};
+use crate::html_page_utils::{unordered_list, HtmlEscaped, ListOptions};
+use crate::preview::{self, data, document, hex, markdown, media, text, PreviewKind, PREVIEW_JS};
+use crate::search::{MatchMode, MatchScope, Matcher, SearchIndex};
+use crate::storage::{content_recursively, precompute_checksums};
 use crate::style::STYLE_CSS;
+use crate::thumbnail::{self, ThumbnailCache};
 use askama::Template;
 use axum::{
-    body::Bytes,
+    body::{Body, Bytes},
     extract::{Query, Request, State},
     http::{header, HeaderValue, StatusCode},
     response::{Html, IntoResponse, Response},
     routing::get,
-    Router,
+    Json, Router,
 };
-use serde::Deserialize;
-use std::{collections::HashMap, sync::Arc};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, convert::Infallible, fmt::Write as _, sync::Arc};
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::{wrappers::ReceiverStream, StreamExt};
 use tower::ServiceExt;
 use tower_http::{services::ServeFile, trace::TraceLayer};
 
@@ -23,7 +31,17 @@ const APP_TITLE: &str = concat!("Minicloud v", env!("CARGO_PKG_VERSION"));
 #[derive(Clone)]
 pub struct TransmitterState {
     pub fs_objects: Arc<HashMap<u64, Arc<FsObject>>>,
+    /// Shared directories by ID, for generating checksum manifests.
+    pub directories: Arc<HashMap<u64, Arc<FsObject>>>,
     pub index_page: Html<Bytes>,
+    pub search_index: Arc<SearchIndex>,
+    /// `None` if the content search is disabled.
+    pub content_search_max_size: Option<u64>,
+    pub thumbnails: Arc<ThumbnailCache>,
+    pub size_units: SizeUnits,
+    pub documents: Arc<document::DocumentTools>,
+    /// Text published with `--text`.
+    pub shared_text: Option<Arc<str>>,
 }
 
 #[derive(Template)]
@@ -31,6 +49,11 @@ pub struct TransmitterState {
 struct TransmitterTemplate<'a> {
     title: &'a str,
     files_list: &'a str,
+    sort: &'a str,
+    dirs_first: bool,
+    content_search: bool,
+    /// HTML-escaped text published with `--text`.
+    shared_text: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -38,26 +61,154 @@ pub struct Params {
     id: u64,
 }
 
+/// Number of thumbnails kept in memory (about 15 KiB each).
+const THUMBNAIL_CACHE_CAPACITY: usize = 4096;
+
+#[derive(Deserialize)]
+pub struct ArchiveMemberParams {
+    id: u64,
+    path: String,
+    #[serde(default)]
+    inline: bool,
+}
+
+#[derive(Serialize)]
+pub struct ArchiveListing {
+    entries: Vec<ArchiveEntry>,
+    truncated: bool,
+}
+
+#[derive(Deserialize)]
+pub struct PreviewParams {
+    id: u64,
+    mode: Option<PreviewKind>,
+    /// Table page, starting from 1.
+    page: Option<usize>,
+    /// Table column to sort by.
+    sort: Option<usize>,
+    #[serde(default)]
+    desc: bool,
+    /// Overrides table header detection.
+    header: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct TextChunkParams {
+    id: u64,
+    offset: u64,
+    line: usize,
+}
+
+#[derive(Deserialize)]
+pub struct ChecksumParams {
+    id: u64,
+    #[serde(default)]
+    algo: HashAlgo,
+}
+
+#[derive(Deserialize)]
+pub struct HexPageParams {
+    id: u64,
+    offset: u64,
+}
+
+const SEARCH_DEFAULT_LIMIT: usize = 200;
+const SEARCH_MAX_LIMIT: usize = 1000;
+
+#[derive(Deserialize)]
+pub struct SearchParams {
+    q: String,
+    #[serde(default)]
+    mode: MatchMode,
+    #[serde(default)]
+    scope: MatchScope,
+    limit: Option<usize>,
+}
+
+const GREP_DEFAULT_CONTEXT: usize = 2;
+const GREP_MAX_CONTEXT: usize = 10;
+const GREP_DEFAULT_MATCHES: usize = 500;
+const GREP_MAX_MATCHES: usize = 5000;
+
+#[derive(Deserialize)]
+pub struct GrepParams {
+    q: String,
+    #[serde(default)]
+    mode: MatchMode,
+    context: Option<usize>,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct SearchResult<'a> {
+    name: &'a str,
+    path: &'a str,
+    is_dir: bool,
+    size: u64,
+    download: Option<String>,
+    preview: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SearchResponse<'a> {
+    results: Vec<SearchResult<'a>>,
+    truncated: bool,
+}
+
 pub fn setup(cli_args: &mut Args) -> Result<Router, Box<dyn std::error::Error>> {
     tracing::info!("Transmit mode enabled. Paths: {:?}", cli_args.paths);
     cli_args.prepare_paths();
 
-    if cli_args.paths.is_empty() {
+    let shared_text = match cli_args.text.as_deref() {
+        Some("-") => Some(
+            std::io::read_to_string(std::io::stdin())?
+                .trim_end_matches(['\r', '\n'])
+                .to_owned(),
+        ),
+        Some(text) => Some(text.to_owned()),
+        None => None,
+    };
+
+    if cli_args.paths.is_empty() && shared_text.is_none() {
         return Err("No valid paths provided".into());
     }
 
-    let fs_objects = content_recursively(&cli_args.paths)?;
+    let sort_options = cli_args.sort_options();
+    let fs_objects = content_recursively(&cli_args.paths, sort_options)?;
     let summary = FsSummary::from_objects(&fs_objects);
     println!("{summary}");
 
+    if cli_args.checksums {
+        println!("Computing SHA-256 checksums...");
+        precompute_checksums(&fs_objects);
+    }
+
+    let search_index = SearchIndex::build(&fs_objects);
+    tracing::debug!("Search index built: {} entries", search_index.len());
+
     tracing::debug!("Generating HTML...");
 
+    let documents = document::DocumentTools::detect();
+    let list_options = ListOptions {
+        units: cli_args.size_units(),
+        pdf_thumbnails: documents.pdftoppm,
+    };
+
     let mut hash_map = HashMap::new();
-    let files_list = unordered_list(&fs_objects, &mut hash_map);
+    let files_list = unordered_list(&fs_objects, &mut hash_map, list_options);
+
+    let mut directories = HashMap::new();
+    collect_directories(&fs_objects, &mut directories);
 
     let html_page = TransmitterTemplate {
         title: APP_TITLE,
         files_list: &files_list,
+        sort: sort_options.key.as_str(),
+        dirs_first: sort_options.dirs_first,
+        content_search: cli_args.content_search,
+        shared_text: shared_text
+            .as_deref()
+            .map(|text| HtmlEscaped(text).to_string()),
     }
     .render()?;
 
@@ -72,14 +223,43 @@ pub fn setup(cli_args: &mut Args) -> Result<Router, Box<dyn std::error::Error>>
 
     let state = TransmitterState {
         fs_objects: Arc::new(hash_map),
+        directories: Arc::new(directories),
         index_page: Html(page_bytes),
+        search_index: Arc::new(search_index),
+        content_search_max_size: cli_args
+            .content_search
+            .then_some(cli_args.content_search_max_size.saturating_mul(1024 * 1024)),
+        thumbnails: Arc::new(ThumbnailCache::new(THUMBNAIL_CACHE_CAPACITY)),
+        size_units: cli_args.size_units(),
+        documents: Arc::new(documents),
+        shared_text: shared_text.map(Arc::from),
     };
 
+    if cli_args.content_search {
+        tracing::info!(
+            "Content search enabled for files up to {} MiB",
+            cli_args.content_search_max_size
+        );
+    }
+
     let router = Router::new()
         .route("/", get(show_download_form))
         .route("/dl", get(download_handler))
         .route("/pw", get(preview_handler))
+        .route("/raw", get(raw_handler))
+        .route("/sub", get(subtitle_handler))
+        .route("/text", get(text_chunk_handler))
+        .route("/hex", get(hex_page_handler))
+        .route("/pdf", get(pdf_handler))
+        .route("/archive", get(archive_list_handler))
+        .route("/archive/member", get(archive_member_handler))
+        .route("/thumb", get(thumbnail_handler))
+        .route("/sum", get(checksum_handler))
+        .route("/search", get(search_handler))
+        .route("/grep", get(grep_handler))
+        .route("/clip", get(shared_text_handler))
         .route("/script.js", get(serve_script_js))
+        .route("/preview.js", get(serve_preview_js))
         .route("/style.css", get(serve_style_css))
         .layer(TraceLayer::new_for_http())
         .with_state(state);
@@ -87,6 +267,15 @@ pub fn setup(cli_args: &mut Args) -> Result<Router, Box<dyn std::error::Error>>
     Ok(router)
 }
 
+fn collect_directories(items: &FsObjects, directories: &mut HashMap<u64, Arc<FsObject>>) {
+    for item in items.iter().filter(|item| item.is_dir()) {
+        directories.insert(item.get_hash(), Arc::clone(item));
+        if let Some(content) = &item.content {
+            collect_directories(content, directories);
+        }
+    }
+}
+
 async fn show_download_form(State(state): State<TransmitterState>) -> impl IntoResponse {
     tracing::info!("Root page request");
     state.index_page
@@ -102,6 +291,16 @@ async fn serve_script_js() -> impl IntoResponse {
     )
 }
 
+async fn serve_preview_js() -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, "application/javascript"),
+            (header::CACHE_CONTROL, "public, max-age=3600"),
+        ],
+        PREVIEW_JS,
+    )
+}
+
 async fn serve_style_css() -> impl IntoResponse {
     (
         [
@@ -112,6 +311,18 @@ async fn serve_style_css() -> impl IntoResponse {
     )
 }
 
+/// Serves the text published with `--text` as plain text.
+async fn shared_text_handler(
+    State(state): State<TransmitterState>,
+) -> Result<Response, StatusCode> {
+    let text = state.shared_text.ok_or(StatusCode::NOT_FOUND)?;
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+        text.to_string(),
+    )
+        .into_response())
+}
+
 pub async fn download_handler(
     State(state): State<TransmitterState>,
     Query(params): Query<Params>,
@@ -133,27 +344,190 @@ pub async fn download_handler(
         })?
         .into_response();
 
-    let raw_name = fs_object.name();
+    if let Some(val) = content_disposition("attachment", fs_object.name()) {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_DISPOSITION, val);
+    }
+
+    Ok(response)
+}
+
+fn content_disposition(disposition_type: &str, raw_name: &str) -> Option<HeaderValue> {
     let ascii_name = raw_name.replace('"', "\\\"");
-    let encoded_name = utf8_percent_encode(&raw_name);
+    let encoded_name = utf8_percent_encode(raw_name);
 
     let disposition = format!(
-        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
-        ascii_name, encoded_name
+        "{}; filename=\"{}\"; filename*=UTF-8''{}",
+        disposition_type, ascii_name, encoded_name
     );
 
-    if let Ok(val) = HeaderValue::try_from(disposition) {
-        response
-            .headers_mut()
-            .insert(header::CONTENT_DISPOSITION, val);
+    HeaderValue::try_from(disposition).ok()
+}
+
+/// Returns the checksum of a file in `sha256sum` format, or the checksum manifest
+/// (e.g. `SHA256SUMS`) of a directory.
+pub async fn checksum_handler(
+    State(state): State<TransmitterState>,
+    Query(params): Query<ChecksumParams>,
+) -> Result<Response, StatusCode> {
+    let algo = params.algo;
+
+    if let Some(fs_object) = state.fs_objects.get(&params.id) {
+        tracing::info!(
+            "Checksum request ({}): {}",
+            algo.as_str(),
+            fs_object.path.display()
+        );
+
+        let file = Arc::clone(fs_object);
+        let hash = tokio::task::spawn_blocking(move || checksum::file_checksum(&file, algo))
+            .await
+            .map_err(|err| {
+                tracing::error!("Checksum task failed: {err}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+            .map_err(|err| {
+                tracing::error!("Failed to hash {}: {err}", fs_object.path.display());
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+        let mut line = String::new();
+        checksum::manifest_line(&mut line, &hash, fs_object.name());
+        return Ok(([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], line).into_response());
+    }
+
+    let dir = state.directories.get(&params.id).ok_or_else(|| {
+        tracing::warn!("Checksum item not found. ID = {}", params.id);
+        StatusCode::NOT_FOUND
+    })?;
+
+    tracing::info!(
+        "Checksum manifest request ({}): {}",
+        algo.as_str(),
+        dir.path.display()
+    );
+
+    let task_dir = Arc::clone(dir);
+    let manifest =
+        tokio::task::spawn_blocking(move || checksum::directory_manifest(&task_dir, algo))
+            .await
+            .map_err(|err| {
+                tracing::error!("Checksum manifest task failed: {err}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+    let mut response = manifest.into_response();
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    if let Some(val) = content_disposition("inline", algo.manifest_name()) {
+        headers.insert(header::CONTENT_DISPOSITION, val);
     }
 
     Ok(response)
 }
 
-pub async fn preview_handler(
+/// Lists the members of an archive for expanding it in the listing.
+pub async fn archive_list_handler(
     State(state): State<TransmitterState>,
     Query(params): Query<Params>,
+) -> Result<Response, StatusCode> {
+    let (fs_object, format) = find_archive(&state, params.id)?;
+
+    tracing::info!("Archive listing request: {}", fs_object.path.display());
+
+    let path = fs_object.path.clone();
+    let (entries, truncated) = tokio::task::spawn_blocking(move || archive::list(&path, format))
+        .await
+        .map_err(|err| {
+            tracing::error!("Archive listing task failed: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .map_err(|err| {
+            tracing::warn!("Failed to list archive {}: {err}", fs_object.path.display());
+            StatusCode::UNPROCESSABLE_ENTITY
+        })?;
+
+    Ok(Json(ArchiveListing { entries, truncated }).into_response())
+}
+
+/// Streams a single member directly from the archive file.
+pub async fn archive_member_handler(
+    State(state): State<TransmitterState>,
+    Query(params): Query<ArchiveMemberParams>,
+) -> Result<Response, StatusCode> {
+    let (fs_object, format) = find_archive(&state, params.id)?;
+
+    tracing::info!(
+        "Archive member request: {} in {}",
+        params.path,
+        fs_object.path.display()
+    );
+
+    let (size_tx, size_rx) = oneshot::channel();
+    let (tx, rx) = mpsc::channel(4);
+    let path = fs_object.path.clone();
+    let member = params.path.clone();
+    tokio::task::spawn_blocking(move || {
+        archive::stream_member(&path, format, &member, size_tx, tx)
+    });
+
+    let size = size_rx
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|err| {
+            tracing::warn!("Failed to open archive member {:?}: {err}", params.path);
+            match err.kind() {
+                std::io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+                _ => StatusCode::UNPROCESSABLE_ENTITY,
+            }
+        })?;
+
+    let member_path = std::path::Path::new(&params.path);
+    let name = member_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("unnamed");
+    let mime = preview::mime_for(member_path);
+
+    let mut response = Body::from_stream(ReceiverStream::new(rx)).into_response();
+    let headers = response.headers_mut();
+    if let Ok(val) = HeaderValue::try_from(mime.as_ref()) {
+        headers.insert(header::CONTENT_TYPE, val);
+    }
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(size));
+    let disposition_type = if params.inline {
+        "inline"
+    } else {
+        "attachment"
+    };
+    if let Some(val) = content_disposition(disposition_type, name) {
+        headers.insert(header::CONTENT_DISPOSITION, val);
+    }
+
+    Ok(response)
+}
+
+fn find_archive(
+    state: &TransmitterState,
+    id: u64,
+) -> Result<(&Arc<FsObject>, ArchiveFormat), StatusCode> {
+    state
+        .fs_objects
+        .get(&id)
+        .and_then(|fs_object| Some((fs_object, ArchiveFormat::detect(&fs_object.path)?)))
+        .ok_or_else(|| {
+            tracing::warn!("Archive item not found. ID = {}", id);
+            StatusCode::NOT_FOUND
+        })
+}
+
+pub async fn preview_handler(
+    State(state): State<TransmitterState>,
+    Query(params): Query<PreviewParams>,
     request: Request,
 ) -> Result<Response, StatusCode> {
     let fs_object = state.fs_objects.get(&params.id).ok_or_else(|| {
@@ -163,11 +537,78 @@ pub async fn preview_handler(
 
     tracing::info!("Preview request: {}", fs_object.path.display());
 
-    let response = ServeFile::new(&fs_object.path)
+    let kind = match params.mode {
+        Some(mode) => mode,
+        None => PreviewKind::detect_with_content(&fs_object.path).await,
+    };
+
+    match kind {
+        PreviewKind::Video | PreviewKind::Audio => media_page(&state, params.id, fs_object),
+        PreviewKind::Markdown => markdown_page(&state, params.id, fs_object).await,
+        PreviewKind::Table => table_page(&state, &params, fs_object).await,
+        PreviewKind::Json => json_page(&state, params.id, fs_object).await,
+        PreviewKind::Document => document_page(&state, params.id, fs_object),
+        PreviewKind::Text => text_page(&state, params.id, fs_object).await,
+        PreviewKind::Hex => hex_page(&state, params.id, fs_object).await,
+        PreviewKind::Raw => preview::serve_inline(fs_object, request).await,
+    }
+}
+
+fn document_page(
+    state: &TransmitterState,
+    id: u64,
+    fs_object: &FsObject,
+) -> Result<Response, StatusCode> {
+    let viewable = document::is_pdf(&fs_object.path)
+        || (state.documents.soffice && document::is_office(&fs_object.path));
+
+    let page = document::DocumentTemplate {
+        title: APP_TITLE,
+        name: fs_object.name(),
+        id,
+        size: fs_object.size_display(state.size_units).to_string(),
+        pdf_url: viewable.then(|| format!("/pdf?id={id}")),
+    };
+
+    render_page(&page)
+}
+
+/// Serves a PDF inline, converting office documents to PDF first.
+pub async fn pdf_handler(
+    State(state): State<TransmitterState>,
+    Query(params): Query<Params>,
+    request: Request,
+) -> Result<Response, StatusCode> {
+    let fs_object = state.fs_objects.get(&params.id).ok_or_else(|| {
+        tracing::warn!("Document item not found. ID = {}", params.id);
+        StatusCode::NOT_FOUND
+    })?;
+
+    if document::is_pdf(&fs_object.path) {
+        return preview::serve_inline(fs_object, request).await;
+    }
+
+    if !document::is_office(&fs_object.path) {
+        return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    let pdf_path = state
+        .documents
+        .office_to_pdf(params.id, &fs_object.path)
+        .await
+        .map_err(|err| {
+            tracing::warn!(
+                "Failed to convert {} to PDF: {err}",
+                fs_object.path.display()
+            );
+            StatusCode::UNPROCESSABLE_ENTITY
+        })?;
+
+    let response = ServeFile::new_with_mime(&pdf_path, &mime::APPLICATION_PDF)
         .oneshot(request)
         .await
         .map_err(|err| {
-            tracing::error!("Failed to serve file for preview: {err}");
+            tracing::error!("Failed to serve converted document: {err}");
             StatusCode::INTERNAL_SERVER_ERROR
         })?
         .into_response();
@@ -175,6 +616,494 @@ pub async fn preview_handler(
     Ok(response)
 }
 
+async fn markdown_page(
+    state: &TransmitterState,
+    id: u64,
+    fs_object: &FsObject,
+) -> Result<Response, StatusCode> {
+    let path = fs_object.path.clone();
+    let size = fs_object.size;
+    let rendered = tokio::task::spawn_blocking(move || markdown::render_file(&path, size))
+        .await
+        .map_err(|err| {
+            tracing::error!("Markdown preview task failed: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let Some(html) = rendered else {
+        return text_page(state, id, fs_object).await;
+    };
+
+    let page = markdown::MarkdownTemplate {
+        title: APP_TITLE,
+        name: fs_object.name(),
+        id,
+        size: fs_object.size_display(state.size_units).to_string(),
+        html,
+    };
+
+    render_page(&page)
+}
+
+async fn table_page(
+    state: &TransmitterState,
+    params: &PreviewParams,
+    fs_object: &FsObject,
+) -> Result<Response, StatusCode> {
+    let path = fs_object.path.clone();
+    let (header, sort, desc) = (params.header, params.sort, params.desc);
+    let table = tokio::task::spawn_blocking(move || {
+        let mut table = data::Table::read(&path, header)?;
+        if let Some(column) = sort {
+            table.sort(column, desc);
+        }
+        Ok::<_, std::io::Error>(table)
+    })
+    .await
+    .map_err(|err| {
+        tracing::error!("Table preview task failed: {err}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let table = match table {
+        Ok(table) => table,
+        Err(err) => {
+            tracing::warn!(
+                "Failed to parse {} as a table: {err}",
+                fs_object.path.display()
+            );
+            return text_page(state, params.id, fs_object).await;
+        }
+    };
+
+    let id = params.id;
+    let has_header = table.header.is_some();
+    let href = |page: usize, sort: Option<usize>, desc: bool, header: bool| {
+        let mut href = format!("/pw?id={id}&page={page}&header={header}");
+        if let Some(column) = sort {
+            let _ = write!(href, "&sort={column}&desc={desc}");
+        }
+        href
+    };
+
+    let pages = table.rows.len().div_ceil(data::TABLE_PAGE_SIZE).max(1);
+    let page = params.page.unwrap_or(1).clamp(1, pages);
+    let first_row = (page - 1) * data::TABLE_PAGE_SIZE;
+    let last_row = (first_row + data::TABLE_PAGE_SIZE).min(table.rows.len());
+
+    let columns = (0..table.columns)
+        .map(|column| {
+            let sorted = sort == Some(column);
+            data::TableColumn {
+                name: match &table.header {
+                    Some(header) => header[column].clone(),
+                    None => (column + 1).to_string(),
+                },
+                href: href(1, Some(column), sorted && !desc, has_header),
+                indicator: match (sorted, desc) {
+                    (true, false) => " ▲",
+                    (true, true) => " ▼",
+                    _ => "",
+                },
+            }
+        })
+        .collect();
+
+    let page_html = data::TableTemplate {
+        title: APP_TITLE,
+        name: fs_object.name(),
+        id,
+        size: fs_object.size_display(state.size_units).to_string(),
+        columns,
+        rows: &table.rows[first_row..last_row],
+        has_header,
+        header_href: href(1, None, false, !has_header),
+        first_row,
+        total_rows: table.rows.len(),
+        page,
+        pages,
+        prev_href: (page > 1).then(|| href(page - 1, sort, desc, has_header)),
+        next_href: (page < pages).then(|| href(page + 1, sort, desc, has_header)),
+        truncated: table.truncated,
+    };
+
+    render_page(&page_html)
+}
+
+async fn json_page(
+    state: &TransmitterState,
+    id: u64,
+    fs_object: &FsObject,
+) -> Result<Response, StatusCode> {
+    let path = fs_object.path.clone();
+    let size = fs_object.size;
+    let json = tokio::task::spawn_blocking(move || data::JsonPreview::read(&path, size))
+        .await
+        .map_err(|err| {
+            tracing::error!("JSON preview task failed: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let Some(json) = json else {
+        return text_page(state, id, fs_object).await;
+    };
+
+    let page = data::JsonTemplate {
+        title: APP_TITLE,
+        name: fs_object.name(),
+        id,
+        size: fs_object.size_display(state.size_units).to_string(),
+        json,
+    };
+
+    render_page(&page)
+}
+
+async fn text_page(
+    state: &TransmitterState,
+    id: u64,
+    fs_object: &FsObject,
+) -> Result<Response, StatusCode> {
+    let chunk = read_text_chunk(fs_object, 0, 1).await?;
+
+    let page = text::TextTemplate {
+        title: APP_TITLE,
+        name: fs_object.name(),
+        id,
+        size: fs_object.size_display(state.size_units).to_string(),
+        chunk,
+    };
+
+    render_page(&page)
+}
+
+async fn read_text_chunk(
+    fs_object: &FsObject,
+    offset: u64,
+    first_line: usize,
+) -> Result<text::TextChunk, StatusCode> {
+    let path = fs_object.path.clone();
+    let file_size = fs_object.size;
+
+    tokio::task::spawn_blocking(move || text::render_chunk(&path, file_size, offset, first_line))
+        .await
+        .map_err(|err| {
+            tracing::error!("Text preview task failed: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .map_err(|err| {
+            tracing::error!(
+                "Failed to read {} for preview: {err}",
+                fs_object.path.display()
+            );
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+/// Returns the next highlighted chunk of a text file for the "load more" button.
+pub async fn text_chunk_handler(
+    State(state): State<TransmitterState>,
+    Query(params): Query<TextChunkParams>,
+) -> Result<Response, StatusCode> {
+    let fs_object = state.fs_objects.get(&params.id).ok_or_else(|| {
+        tracing::warn!("Text item not found. ID = {}", params.id);
+        StatusCode::NOT_FOUND
+    })?;
+
+    if params.offset > fs_object.size || params.line == 0 {
+        return Err(StatusCode::RANGE_NOT_SATISFIABLE);
+    }
+
+    let chunk = read_text_chunk(fs_object, params.offset, params.line).await?;
+
+    Ok(Json(chunk).into_response())
+}
+
+/// Returns one page of the hex dump as JSON.
+pub async fn hex_page_handler(
+    State(state): State<TransmitterState>,
+    Query(params): Query<HexPageParams>,
+) -> Result<Response, StatusCode> {
+    let fs_object = state.fs_objects.get(&params.id).ok_or_else(|| {
+        tracing::warn!("Hex item not found. ID = {}", params.id);
+        StatusCode::NOT_FOUND
+    })?;
+
+    let page = read_hex_page(fs_object, params.offset).await?;
+
+    Ok(Json(page).into_response())
+}
+
+async fn hex_page(
+    state: &TransmitterState,
+    id: u64,
+    fs_object: &FsObject,
+) -> Result<Response, StatusCode> {
+    let page = read_hex_page(fs_object, 0).await?;
+
+    let page = hex::HexTemplate {
+        title: APP_TITLE,
+        name: fs_object.name(),
+        id,
+        size: fs_object.size_display(state.size_units).to_string(),
+        file_size: fs_object.size,
+        page,
+    };
+
+    render_page(&page)
+}
+
+async fn read_hex_page(fs_object: &FsObject, offset: u64) -> Result<hex::HexPage, StatusCode> {
+    let path = fs_object.path.clone();
+    let file_size = fs_object.size;
+
+    tokio::task::spawn_blocking(move || hex::render_page(&path, file_size, offset))
+        .await
+        .map_err(|err| {
+            tracing::error!("Hex preview task failed: {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .map_err(|err| {
+            tracing::error!(
+                "Failed to read {} for hex preview: {err}",
+                fs_object.path.display()
+            );
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+fn media_page(
+    state: &TransmitterState,
+    id: u64,
+    fs_object: &FsObject,
+) -> Result<Response, StatusCode> {
+    let Some((kind, mime)) = media::media_type(&fs_object.path) else {
+        return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    };
+
+    let page = media::MediaTemplate {
+        title: APP_TITLE,
+        name: fs_object.name(),
+        id,
+        size: fs_object.size_display(state.size_units).to_string(),
+        mime,
+        video: kind == media::MediaKind::Video,
+        subtitles: media::find_subtitles(fs_object, &state.fs_objects),
+    };
+
+    render_page(&page)
+}
+
+fn render_page(template: &impl Template) -> Result<Response, StatusCode> {
+    let page = template.render().map_err(|err| {
+        tracing::error!("Template render error: {err}");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Html(page).into_response())
+}
+
+/// Serves the file inline with the MIME type browsers can play or display.
+pub async fn raw_handler(
+    State(state): State<TransmitterState>,
+    Query(params): Query<Params>,
+    request: Request,
+) -> Result<Response, StatusCode> {
+    let fs_object = state.fs_objects.get(&params.id).ok_or_else(|| {
+        tracing::warn!("Raw item not found. ID = {}", params.id);
+        StatusCode::NOT_FOUND
+    })?;
+
+    tracing::debug!("Raw request: {}", fs_object.path.display());
+
+    preview::serve_inline(fs_object, request).await
+}
+
+/// Serves a subtitle file as WebVTT, converting SubRip files on the fly.
+pub async fn subtitle_handler(
+    State(state): State<TransmitterState>,
+    Query(params): Query<Params>,
+) -> Result<Response, StatusCode> {
+    let fs_object = state
+        .fs_objects
+        .get(&params.id)
+        .filter(|fs_object| media::is_subtitle(&fs_object.path))
+        .ok_or_else(|| {
+            tracing::warn!("Subtitle item not found. ID = {}", params.id);
+            StatusCode::NOT_FOUND
+        })?;
+
+    let content = tokio::fs::read(&fs_object.path).await.map_err(|err| {
+        tracing::error!(
+            "Failed to read subtitles {}: {err}",
+            fs_object.path.display()
+        );
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let content = String::from_utf8_lossy(&content);
+
+    let vtt = match preview::extension(&fs_object.path).as_deref() {
+        Some("srt") => media::srt_to_vtt(&content),
+        _ => content.into_owned(),
+    };
+
+    Ok(([(header::CONTENT_TYPE, "text/vtt; charset=utf-8")], vtt).into_response())
+}
+
+pub async fn thumbnail_handler(
+    State(state): State<TransmitterState>,
+    Query(params): Query<Params>,
+) -> Result<Response, StatusCode> {
+    let fs_object = state.fs_objects.get(&params.id).ok_or_else(|| {
+        tracing::warn!("Thumbnail item not found. ID = {}", params.id);
+        StatusCode::NOT_FOUND
+    })?;
+
+    let is_pdf = document::is_pdf(&fs_object.path);
+    let supported = thumbnail::is_image(&fs_object.path) || (is_pdf && state.documents.pdftoppm);
+    if !supported {
+        return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    let thumbnail = match state.thumbnails.get(params.id) {
+        Some(cached) => cached,
+        None => {
+            tracing::debug!("Generating thumbnail: {}", fs_object.path.display());
+            let first_page = if is_pdf {
+                let page = state
+                    .documents
+                    .first_page_png(&fs_object.path)
+                    .await
+                    .map_err(|err| {
+                        tracing::warn!(
+                            "Failed to render first page of {}: {err}",
+                            fs_object.path.display()
+                        );
+                        StatusCode::UNPROCESSABLE_ENTITY
+                    })?;
+                Some(page)
+            } else {
+                None
+            };
+
+            let path = fs_object.path.clone();
+            let generated = tokio::task::spawn_blocking(move || match first_page {
+                Some(page) => thumbnail::generate_from_memory(&page),
+                None => thumbnail::generate(&path),
+            })
+            .await
+            .map_err(|err| {
+                tracing::error!("Thumbnail task failed: {err}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+            .map_err(|err| {
+                tracing::warn!(
+                    "Failed to generate thumbnail for {}: {err}",
+                    fs_object.path.display()
+                );
+                StatusCode::UNPROCESSABLE_ENTITY
+            })?;
+
+            let generated = Bytes::from(generated);
+            state.thumbnails.insert(params.id, generated.clone());
+            generated
+        }
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/jpeg"),
+            (header::CACHE_CONTROL, "public, max-age=86400"),
+        ],
+        thumbnail,
+    )
+        .into_response())
+}
+
+pub async fn search_handler(
+    State(state): State<TransmitterState>,
+    Query(params): Query<SearchParams>,
+) -> Result<Response, (StatusCode, String)> {
+    tracing::info!("Search request: {:?} ({:?})", params.q, params.mode);
+
+    let matcher = Matcher::new(&params.q, params.mode).map_err(|err| {
+        tracing::warn!("Invalid search query {:?}: {err}", params.q);
+        (StatusCode::BAD_REQUEST, err)
+    })?;
+
+    let limit = params
+        .limit
+        .unwrap_or(SEARCH_DEFAULT_LIMIT)
+        .min(SEARCH_MAX_LIMIT);
+    let (entries, truncated) = state.search_index.search(&matcher, params.scope, limit);
+
+    let results = entries
+        .into_iter()
+        .map(|entry| {
+            let is_file = !entry.is_dir && state.fs_objects.contains_key(&entry.id);
+            SearchResult {
+                name: entry.name(),
+                path: &entry.path,
+                is_dir: entry.is_dir,
+                size: entry.size,
+                download: is_file.then(|| format!("/dl?id={}", entry.id)),
+                preview: is_file.then(|| format!("/pw?id={}", entry.id)),
+            }
+        })
+        .collect();
+
+    Ok(Json(SearchResponse { results, truncated }).into_response())
+}
+
+pub async fn grep_handler(
+    State(state): State<TransmitterState>,
+    Query(params): Query<GrepParams>,
+) -> Result<Response, (StatusCode, String)> {
+    let Some(max_file_size) = state.content_search_max_size else {
+        tracing::warn!("Content search request while the feature is disabled");
+        return Err((
+            StatusCode::NOT_FOUND,
+            "Content search is disabled".to_string(),
+        ));
+    };
+
+    tracing::info!("Content search request: {:?} ({:?})", params.q, params.mode);
+
+    if params.q.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Empty query".to_string()));
+    }
+
+    let matcher = Matcher::new(&params.q, params.mode).map_err(|err| {
+        tracing::warn!("Invalid content search query {:?}: {err}", params.q);
+        (StatusCode::BAD_REQUEST, err)
+    })?;
+
+    let limits = GrepLimits {
+        max_file_size,
+        context: params
+            .context
+            .unwrap_or(GREP_DEFAULT_CONTEXT)
+            .min(GREP_MAX_CONTEXT),
+        max_matches: params
+            .limit
+            .unwrap_or(GREP_DEFAULT_MATCHES)
+            .min(GREP_MAX_MATCHES),
+    };
+
+    let (tx, rx) = mpsc::channel(16);
+    let index = Arc::clone(&state.search_index);
+    let fs_objects = Arc::clone(&state.fs_objects);
+    tokio::task::spawn_blocking(move || stream_matches(index, fs_objects, matcher, limits, tx));
+
+    let stream = ReceiverStream::new(rx).map(Ok::<_, Infallible>);
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson; charset=utf-8")],
+        Body::from_stream(stream),
+    )
+        .into_response())
+}
+
 fn utf8_percent_encode(s: &str) -> String {
     let mut encoded = String::new();
     for byte in s.bytes() {
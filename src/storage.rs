@@ -5,32 +5,36 @@ use std::{
     sync::Arc,
 };
 
-use crate::fs_object::{FsObject, FsObjects};
+use crate::checksum::{self, HashAlgo};
+use crate::fs_object::{FsObject, FsObjects, SortOptions};
 
 /// Recursively scans a slice of paths sequentially in a single thread.
 ///
 /// Optimized to minimize system calls (`stat`/`lstat`) and memory allocations.
-pub fn content_recursively(paths: &[PathBuf]) -> Result<FsObjects> {
+/// The entries of every directory (and the roots themselves) are ordered according to `sort`.
+pub fn content_recursively(paths: &[PathBuf], sort: SortOptions) -> Result<FsObjects> {
     let mut fs_objects_root = Vec::with_capacity(paths.len());
 
     for path in paths {
-        match process_root_path(path.clone()) {
+        match process_root_path(path.clone(), sort) {
             Ok(fs_object) => fs_objects_root.push(Arc::new(fs_object)),
             Err(err) => tracing::warn!("{err}: {:?}", path),
         }
     }
 
+    sort.sort(&mut fs_objects_root);
+
     Ok(fs_objects_root)
 }
 
 /// Processes a root path (which comes directly as a `PathBuf`, not from `read_dir`).
-fn process_root_path(path: PathBuf) -> Result<FsObject> {
+fn process_root_path(path: PathBuf, sort: SortOptions) -> Result<FsObject> {
     let metadata = path.symlink_metadata()?;
     let is_dir = metadata.is_dir();
     let is_symlink = metadata.is_symlink();
 
     let content = if is_dir && !is_symlink {
-        scan_dir_content(&path)
+        scan_dir_content(&path, sort)
     } else {
         None
     };
@@ -40,7 +44,7 @@ fn process_root_path(path: PathBuf) -> Result<FsObject> {
 
 /// Reads a directory sequentially and constructs child [`FsObject`]s directly
 /// without intermediate `Vec<PathBuf>` allocations.
-fn scan_dir_content(path: &Path) -> Option<FsObjects> {
+fn scan_dir_content(path: &Path, sort: SortOptions) -> Option<FsObjects> {
     let read_dir = match read_dir(path) {
         Ok(rd) => rd,
         Err(err) => {
@@ -60,7 +64,7 @@ fn scan_dir_content(path: &Path) -> Option<FsObjects> {
             }
         };
 
-        match process_dir_entry(entry) {
+        match process_dir_entry(entry, sort) {
             Ok(fs_object) => children.push(Arc::new(fs_object)),
             Err(err) => tracing::warn!("Failed to process entry in {:?}: {err}", path),
         }
@@ -69,11 +73,12 @@ fn scan_dir_content(path: &Path) -> Option<FsObjects> {
     if children.is_empty() {
         None
     } else {
+        sort.sort(&mut children);
         Some(children)
     }
 }
 
-fn process_dir_entry(entry: DirEntry) -> Result<FsObject> {
+fn process_dir_entry(entry: DirEntry, sort: SortOptions) -> Result<FsObject> {
     let metadata = entry.metadata()?;
     let is_dir = metadata.is_dir();
     let is_symlink = metadata.is_symlink();
@@ -81,10 +86,36 @@ fn process_dir_entry(entry: DirEntry) -> Result<FsObject> {
     let path = entry.path();
 
     let content = if is_dir && !is_symlink {
-        scan_dir_content(&path)
+        scan_dir_content(&path, sort)
     } else {
         None
     };
 
     Ok(FsObject::new(path, metadata, content))
 }
+
+/// Computes the SHA-256 of every file in advance, so that it can be shown in the listing.
+///
+/// Files are hashed on all available cores; unreadable ones are skipped with a warning.
+pub fn precompute_checksums(fs_objects: &FsObjects) {
+    let files: Vec<&FsObject> = fs_objects
+        .iter()
+        .flat_map(|root| root.recursive_iter())
+        .filter(|item| item.is_file())
+        .collect();
+
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = files.len().div_ceil(threads).max(1);
+
+    std::thread::scope(|scope| {
+        for chunk in files.chunks(chunk_size) {
+            scope.spawn(move || {
+                for file in chunk {
+                    if let Err(err) = checksum::file_checksum(file, HashAlgo::Sha256) {
+                        tracing::warn!("Failed to hash {:?}: {err}", file.path);
+                    }
+                }
+            });
+        }
+    });
+}
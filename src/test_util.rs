@@ -0,0 +1,31 @@
+//! Helpers shared by the unit tests.
+
+use crate::checksum::to_hex;
+use std::path::{Path, PathBuf};
+
+/// Directory unique to one test, removed when dropped, also if the test fails.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        let mut bytes = [0; 8];
+        getrandom::fill(&mut bytes).unwrap();
+        let path = std::env::temp_dir().join(format!("minicloud-test-{}", to_hex(&bytes)));
+        std::fs::create_dir(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
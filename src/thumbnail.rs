@@ -0,0 +1,154 @@
+//! Downscaled previews for the image gallery.
+
+use axum::body::Bytes;
+use image::{codecs::jpeg::JpegEncoder, DynamicImage, ImageDecoder, ImageReader, ImageResult};
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+    sync::Mutex,
+};
+
+/// Longest side of a generated thumbnail in pixels.
+pub const THUMBNAIL_SIZE: u32 = 256;
+
+const THUMBNAIL_QUALITY: u8 = 80;
+
+/// Extensions of the image formats thumbnails can be generated for.
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp", "bmp"];
+
+/// Checks by extension whether the file is an image suitable for the gallery.
+pub fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            IMAGE_EXTENSIONS
+                .iter()
+                .any(|known| ext.eq_ignore_ascii_case(known))
+        })
+}
+
+/// Decodes an image, applies its EXIF orientation and encodes a JPEG thumbnail.
+pub fn generate(path: &Path) -> ImageResult<Vec<u8>> {
+    let mut decoder = ImageReader::open(path)?
+        .with_guessed_format()?
+        .into_decoder()?;
+    let orientation = decoder.orientation()?;
+
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+
+    encode_thumbnail(&image)
+}
+
+/// Generates a JPEG thumbnail from an encoded image, e.g. a rendered document page.
+pub fn generate_from_memory(data: &[u8]) -> ImageResult<Vec<u8>> {
+    encode_thumbnail(&image::load_from_memory(data)?)
+}
+
+fn encode_thumbnail(image: &DynamicImage) -> ImageResult<Vec<u8>> {
+    let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).into_rgb8();
+
+    let mut buf = Vec::new();
+    thumbnail.write_with_encoder(JpegEncoder::new_with_quality(&mut buf, THUMBNAIL_QUALITY))?;
+
+    Ok(buf)
+}
+
+/// In-memory cache of generated thumbnails with first-in-first-out eviction.
+pub struct ThumbnailCache {
+    capacity: usize,
+    inner: Mutex<CacheInner>,
+}
+
+#[derive(Default)]
+struct CacheInner {
+    thumbnails: HashMap<u64, Bytes>,
+    order: VecDeque<u64>,
+}
+
+impl ThumbnailCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::default(),
+        }
+    }
+
+    pub fn get(&self, id: u64) -> Option<Bytes> {
+        let inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        inner.thumbnails.get(&id).cloned()
+    }
+
+    pub fn insert(&self, id: u64, thumbnail: Bytes) {
+        let mut inner = self.inner.lock().unwrap_or_else(|err| err.into_inner());
+        if inner.thumbnails.insert(id, thumbnail).is_some() {
+            return;
+        }
+
+        inner.order.push_back(id);
+        while inner.order.len() > self.capacity {
+            if let Some(evicted) = inner.order.pop_front() {
+                inner.thumbnails.remove(&evicted);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use image::{ImageFormat, RgbImage};
+    use std::io::Cursor;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut buf = Vec::new();
+        RgbImage::new(width, height)
+            .write_to(&mut Cursor::new(&mut buf), ImageFormat::Png)
+            .unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_is_image() {
+        assert!(is_image(Path::new("photos/IMG_001.JPG")));
+        assert!(is_image(Path::new("diagram.webp")));
+        assert!(!is_image(Path::new("notes.txt")));
+        assert!(!is_image(Path::new("jpg")));
+    }
+
+    #[test]
+    fn test_generate() {
+        let thumbnail = generate_from_memory(&png(600, 300)).unwrap();
+        let image = image::load_from_memory_with_format(&thumbnail, ImageFormat::Jpeg).unwrap();
+        assert_eq!(
+            (image.width(), image.height()),
+            (THUMBNAIL_SIZE, THUMBNAIL_SIZE / 2)
+        );
+
+        let dir = TempDir::new();
+        let path = dir.join("portrait.png");
+        std::fs::write(&path, png(40, 80)).unwrap();
+        let image = image::load_from_memory(&generate(&path).unwrap()).unwrap();
+        assert_eq!(
+            (image.width(), image.height()),
+            (THUMBNAIL_SIZE / 2, THUMBNAIL_SIZE)
+        );
+
+        std::fs::write(&path, b"not an image").unwrap();
+        assert!(generate(&path).is_err());
+    }
+
+    #[test]
+    fn test_cache_eviction() {
+        let cache = ThumbnailCache::new(2);
+        cache.insert(1, Bytes::from_static(b"one"));
+        cache.insert(2, Bytes::from_static(b"two"));
+        cache.insert(1, Bytes::from_static(b"one again"));
+        cache.insert(3, Bytes::from_static(b"three"));
+
+        assert_eq!(cache.get(1), None);
+        assert_eq!(cache.get(2), Some(Bytes::from_static(b"two")));
+        assert_eq!(cache.get(3), Some(Bytes::from_static(b"three")));
+    }
+}
@@ -0,0 +1,194 @@
+document.addEventListener("DOMContentLoaded", () => {
+    const textPreview = document.getElementById("text-preview");
+    const loadMoreButton = document.getElementById("load-more");
+    if (!textPreview || !loadMoreButton) {
+        return;
+    }
+
+    // How many chunks may be loaded automatically to reach a shared line anchor
+    const MAX_AUTO_LOADS = 20;
+
+    async function loadMore() {
+        const offset = textPreview.dataset.nextOffset;
+        if (offset === undefined) {
+            return false;
+        }
+
+        loadMoreButton.disabled = true;
+        const params = new URLSearchParams({
+            id: textPreview.dataset.id,
+            offset: offset,
+            line: textPreview.dataset.nextLine,
+        });
+        const response = await fetch(`/text?${params}`);
+        if (!response.ok) {
+            loadMoreButton.disabled = false;
+            throw new Error(await response.text());
+        }
+
+        const chunk = await response.json();
+        textPreview.insertAdjacentHTML("beforeend", chunk.html);
+        textPreview.dataset.nextLine = chunk.next_line;
+        if (chunk.next_offset === null) {
+            delete textPreview.dataset.nextOffset;
+            loadMoreButton.remove();
+        } else {
+            textPreview.dataset.nextOffset = chunk.next_offset;
+            loadMoreButton.disabled = false;
+        }
+        return true;
+    }
+
+    // Load chunks until the line from the URL fragment is present
+    async function revealAnchor() {
+        const target = location.hash.slice(1);
+        if (!/^L\d+$/.test(target)) {
+            return;
+        }
+        for (let i = 0; i < MAX_AUTO_LOADS && !document.getElementById(target); i++) {
+            if (!await loadMore()) {
+                break;
+            }
+        }
+        document.getElementById(target)?.scrollIntoView({block: "center"});
+    }
+
+    loadMoreButton.addEventListener("click", () => {
+        loadMore().catch(error => alert(`Failed to load: ${error.message}`));
+    });
+    revealAnchor().catch(error => console.error(error));
+});
+
+document.addEventListener("DOMContentLoaded", () => {
+    const viewer = document.getElementById("document-viewer");
+    if (!viewer) {
+        return;
+    }
+
+    const pageInput = document.getElementById("page-input");
+    const baseUrl = viewer.getAttribute("src").split("#")[0];
+
+    // Browser PDF viewers open the page given in the URL fragment
+    function goTo(page) {
+        page = Math.max(1, page || 1);
+        pageInput.value = page;
+        viewer.src = `${baseUrl}#page=${page}`;
+    }
+
+    document.getElementById("page-prev").addEventListener("click", () => goTo(Number(pageInput.value) - 1));
+    document.getElementById("page-next").addEventListener("click", () => goTo(Number(pageInput.value) + 1));
+    pageInput.addEventListener("change", () => goTo(Number(pageInput.value)));
+});
+
+document.addEventListener("DOMContentLoaded", () => {
+    const tree = document.getElementById("json-tree");
+    const prettyText = document.getElementById("json-pretty-text");
+    if (!tree || !prettyText) {
+        return;
+    }
+
+    const expandButton = document.getElementById("json-expand");
+    const collapseButton = document.getElementById("json-collapse");
+    const prettyToggle = document.getElementById("json-pretty");
+
+    function setExpanded(open) {
+        tree.querySelectorAll("details").forEach((details) => {
+            details.open = open;
+        });
+    }
+
+    expandButton.addEventListener("click", () => setExpanded(true));
+    collapseButton.addEventListener("click", () => setExpanded(false));
+
+    prettyToggle.addEventListener("change", () => {
+        tree.hidden = prettyToggle.checked;
+        prettyText.hidden = !prettyToggle.checked;
+        expandButton.disabled = prettyToggle.checked;
+        collapseButton.disabled = prettyToggle.checked;
+    });
+});
+
+document.addEventListener("DOMContentLoaded", () => {
+    const hexView = document.getElementById("hex-view");
+    if (!hexView) {
+        return;
+    }
+
+    const offsetInput = document.getElementById("hex-offset");
+    const prevButton = document.getElementById("hex-prev");
+    const nextButton = document.getElementById("hex-next");
+    const fileSize = Number(hexView.dataset.size);
+    let prevOffset = hexView.dataset.prev === undefined ? null : Number(hexView.dataset.prev);
+    let nextOffset = hexView.dataset.next === undefined ? null : Number(hexView.dataset.next);
+    prevButton.disabled = prevOffset === null;
+    nextButton.disabled = nextOffset === null;
+
+    function parseOffset(text) {
+        text = text.trim().toLowerCase();
+        const offset = text.startsWith("0x") ? parseInt(text.slice(2), 16) : parseInt(text, 10);
+        return Number.isNaN(offset) ? null : offset;
+    }
+
+    // Fetches only the page containing the offset, the rest of the file stays on the server
+    async function load(offset) {
+        const params = new URLSearchParams({id: hexView.dataset.id, offset: Math.max(0, offset)});
+        const response = await fetch(`/hex?${params}`);
+        if (!response.ok) {
+            throw new Error(await response.text());
+        }
+
+        const page = await response.json();
+        hexView.innerHTML = page.html;
+        hexView.dataset.offset = page.offset;
+        prevOffset = page.prev_offset;
+        nextOffset = page.next_offset;
+        prevButton.disabled = prevOffset === null;
+        nextButton.disabled = nextOffset === null;
+        offsetInput.value = `0x${offset.toString(16)}`;
+        history.replaceState(null, "", `#0x${page.offset.toString(16)}`);
+
+        const line = document.getElementById(`x${(offset - offset % 16).toString(16)}`);
+        line?.classList.add("hex-target");
+        line?.scrollIntoView({block: "center"});
+    }
+
+    function goTo(offset) {
+        load(offset).catch(error => alert(`Failed to load: ${error.message}`));
+    }
+
+    document.getElementById("hex-first").addEventListener("click", () => goTo(0));
+    document.getElementById("hex-last").addEventListener("click", () => goTo(Math.max(0, fileSize - 1)));
+    prevButton.addEventListener("click", () => prevOffset !== null && goTo(prevOffset));
+    nextButton.addEventListener("click", () => nextOffset !== null && goTo(nextOffset));
+    offsetInput.addEventListener("change", () => {
+        const offset = parseOffset(offsetInput.value);
+        if (offset !== null) {
+            goTo(Math.min(offset, Math.max(0, fileSize - 1)));
+        }
+    });
+
+    // The first page is rendered with the page, shared links point to an offset
+    const initial = parseOffset(location.hash.slice(1));
+    if (initial !== null && initial !== 0) {
+        goTo(Math.min(initial, Math.max(0, fileSize - 1)));
+    }
+});
+
+document.addEventListener("DOMContentLoaded", () => {
+    const player = document.getElementById("media-player");
+    const fallback = document.getElementById("media-fallback");
+    if (!player || !fallback) {
+        return;
+    }
+
+    // Browsers report unsupported types up front, or fail on the source once they try
+    const source = player.querySelector("source");
+    const showFallback = () => {
+        player.hidden = true;
+        fallback.hidden = false;
+    };
+    if (player.canPlayType(source.type) === "") {
+        showFallback();
+    }
+    source.addEventListener("error", showFallback);
+});
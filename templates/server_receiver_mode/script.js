@@ -1,83 +1,471 @@
 document.addEventListener("DOMContentLoaded", () => {
     const fileInput = document.getElementById("file-input");
+    const folderInput = document.getElementById("folder-input");
+    const dropOverlay = document.getElementById("drop-overlay");
+    const queueControls = document.getElementById("queue-controls");
+    const queueSummary = document.getElementById("queue-summary");
+    const clearFinishedButton = document.getElementById("clear-finished");
+    const clearQueueButton = document.getElementById("clear-queue");
+    const stopButton = document.getElementById("stop-upload");
     const fileList = document.getElementById("file-list");
     const uploadButton = document.getElementById("upload-button");
     const uploadForm = document.getElementById("upload-form");
+    const uploadStatus = document.getElementById("upload-status");
+    const senderInput = document.getElementById("sender-input");
+    const textForm = document.getElementById("text-form");
+    const textInput = document.getElementById("text-input");
+    const textButton = document.getElementById("text-button");
 
-    let filesArray = [];
+    const maxFileSize = Number(uploadForm.dataset.maxFileSize) || Infinity;
+    const maxTotalSize = Number(uploadForm.dataset.maxTotalSize) || Infinity;
 
-    // Event listener to handle file selection
-    fileInput.addEventListener("change", (event) => {
-        // Add selected files to the array
-        for (const file of event.target.files) {
-            filesArray.push(file);
+    // Upload queue. Every item is {file, path, state, xhr, cancelled, elements}, where path
+    // includes the dropped folders and state is one of "pending", "hashing", "uploading",
+    // "done" or "failed"
+    let queue = [];
+    let uploading = false;
+    // Set by the Stop button to leave the remaining files waiting
+    let stopping = false;
+
+    function showStatus(message, isError) {
+        uploadStatus.textContent = message;
+        uploadStatus.classList.toggle("error", isError);
+        uploadStatus.hidden = !message;
+    }
+
+    function formatSize(bytes) {
+        const units = ["B", "KiB", "MiB", "GiB", "TiB"];
+        let unit = 0;
+        while (bytes >= 1024 && unit + 1 < units.length) {
+            bytes /= 1024;
+            unit++;
+        }
+        return unit === 0 ? `${bytes} B` : `${bytes.toFixed(1)} ${units[unit]}`;
+    }
+
+    function formatDuration(seconds) {
+        seconds = Math.ceil(seconds);
+        const minutes = Math.floor(seconds / 60);
+        return minutes >= 60
+            ? `${Math.floor(minutes / 60)} h ${minutes % 60} min`
+            : `${minutes}:${String(seconds % 60).padStart(2, "0")}`;
+    }
+
+    // Explains why the server refused the upload
+    function describeRejection(status, message) {
+        message = message.trim();
+        switch (status) {
+            case 413:
+                return `Too large: ${message.includes(" ") ? message : `the upload exceeds ${formatSize(maxTotalSize)} per request`}`;
+            case 422:
+                return `Rejected: ${message}`;
+            case 415:
+                return `File type not accepted: ${message}`;
+            case 507:
+                return `Not enough storage on the server: ${message}`;
+            default:
+                return `Failed to upload: ${message || `HTTP ${status}`}`;
+        }
+    }
+
+    function describeSaved(file) {
+        const verified = file.verified ? ", checksum verified" : "";
+        if (file.extracted) {
+            const {dir, files, skipped, archive_kept} = file.extracted;
+            const skippedNote = skipped ? ` (${skipped} not accepted)` : "";
+            return `Extracted ${files} ${files === 1 ? "file" : "files"}${skippedNote} to ${dir}/${archive_kept ? `, archive saved as ${file.path}` : ""}${verified}`;
+        }
+        if (file.extract_error) {
+            return `Saved as ${file.path}${verified}. ${file.extract_error}`;
+        }
+        return `Saved as ${file.path}${verified}`;
+    }
+
+    // Adds files to the queue, leaving out the ones the server would reject.
+    // `files` are {file, path} objects
+    function addFiles(files) {
+        // Finished uploads make room for the new selection
+        if (!uploading) {
+            queue = queue.filter(item => item.state !== "done");
+        }
+
+        const rejected = [];
+        for (const {file, path} of files) {
+            if (file.size > maxFileSize || file.size > maxTotalSize) {
+                rejected.push(path);
+            } else {
+                queue.push({file, path, state: "pending", xhr: null, cancelled: false, elements: null});
+            }
+        }
+        showStatus(rejected.length === 0 ? "" :
+            `Larger than the maximum file size of ${formatSize(Math.min(maxFileSize, maxTotalSize))}: ${rejected.join(", ")}`, true);
+
+        updateFileList();
+    }
+
+    // Event listeners to handle file and folder selection
+    for (const input of [fileInput, folderInput]) {
+        input.addEventListener("change", () => {
+            addFiles([...input.files].map(file => ({file, path: file.webkitRelativePath || file.name})));
+            // Clear the file input value to allow re-selecting the same files
+            input.value = "";
+        });
+    }
+
+    // Lists the files of a dropped file or folder
+    async function readEntry(entry, parent) {
+        if (entry.isFile) {
+            const file = await new Promise((resolve, reject) => entry.file(resolve, reject));
+            return [{file, path: parent + file.name}];
+        }
+        if (!entry.isDirectory) {
+            return [];
+        }
+
+        // Directory entries come in batches until an empty one
+        const reader = entry.createReader();
+        const children = [];
+        for (;;) {
+            const batch = await new Promise((resolve, reject) => reader.readEntries(resolve, reject));
+            if (batch.length === 0) {
+                break;
+            }
+            children.push(...batch);
+        }
+
+        const files = [];
+        for (const child of children) {
+            files.push(...await readEntry(child, `${parent}${entry.name}/`));
+        }
+        return files;
+    }
+
+    // The whole window is a drop zone
+    let dragDepth = 0;
+    const isFileDrag = (event) => event.dataTransfer?.types.includes("Files");
+
+    window.addEventListener("dragenter", (event) => {
+        if (isFileDrag(event)) {
+            dragDepth++;
+            dropOverlay.hidden = false;
+        }
+    });
+    window.addEventListener("dragleave", (event) => {
+        if (isFileDrag(event) && --dragDepth <= 0) {
+            dragDepth = 0;
+            dropOverlay.hidden = true;
+        }
+    });
+    window.addEventListener("dragover", (event) => {
+        if (isFileDrag(event)) {
+            event.preventDefault();
+            event.dataTransfer.dropEffect = "copy";
+        }
+    });
+    window.addEventListener("drop", async (event) => {
+        if (!isFileDrag(event)) {
+            return;
+        }
+        event.preventDefault();
+        dragDepth = 0;
+        dropOverlay.hidden = true;
+
+        // Entries must be taken before the first await, the drop data is gone after the event
+        const entries = [...event.dataTransfer.items]
+            .filter(item => item.kind === "file")
+            .map(item => item.webkitGetAsEntry?.());
+        const droppedFiles = [...event.dataTransfer.files];
+
+        try {
+            if (entries.length > 0 && entries.every(entry => entry)) {
+                const files = await Promise.all(entries.map(entry => readEntry(entry, "")));
+                addFiles(files.flat());
+            } else {
+                addFiles(droppedFiles.map(file => ({file, path: file.name})));
+            }
+        } catch (error) {
+            console.error("Error:", error);
+            showStatus("Failed to read the dropped files.", true);
+        }
+    });
+
+    // Pasted images are queued as timestamped PNG files
+    let lastPasteStamp = "";
+    let pasteCounter = 0;
+
+    function pastedFileName() {
+        const stamp = new Date().toISOString().slice(0, 19).replace(/[-:]/g, "").replace("T", "-");
+        pasteCounter = stamp === lastPasteStamp ? pasteCounter + 1 : 1;
+        lastPasteStamp = stamp;
+        return pasteCounter === 1 ? `pasted-${stamp}.png` : `pasted-${stamp}-${pasteCounter}.png`;
+    }
+
+    async function toPng(blob) {
+        if (blob.type === "image/png") {
+            return blob;
+        }
+        const bitmap = await createImageBitmap(blob);
+        const canvas = document.createElement("canvas");
+        canvas.width = bitmap.width;
+        canvas.height = bitmap.height;
+        canvas.getContext("2d").drawImage(bitmap, 0, 0);
+        return new Promise((resolve, reject) =>
+            canvas.toBlob(png => png ? resolve(png) : reject(new Error("PNG encoding failed")), "image/png"));
+    }
+
+    document.addEventListener("paste", async (event) => {
+        const images = [...event.clipboardData.items]
+            .filter(item => item.kind === "file" && item.type.startsWith("image/"))
+            .map(item => item.getAsFile());
+        // Pasted text goes where it was pasted, e.g. into the text pad
+        const intoTextField = event.target.closest?.("input, textarea")
+            && event.clipboardData.types.includes("text/plain");
+        if (images.length === 0 || intoTextField) {
+            return;
         }
+        event.preventDefault();
 
-        // Update the displayed file list
+        try {
+            const files = [];
+            for (const image of images) {
+                const png = await toPng(image);
+                files.push(new File([png], pastedFileName(), {type: "image/png"}));
+            }
+            addFiles(files.map(file => ({file, path: file.name})));
+        } catch (error) {
+            console.error("Error:", error);
+            showStatus("The pasted image can't be converted to PNG.", true);
+        }
+    });
+
+    // Updates the Upload button and the queue controls
+    function updateControls() {
+        const waiting = queue.filter(item => item.state === "pending" || item.state === "failed");
+        const finished = queue.filter(item => item.state === "done");
+        const waitingSize = waiting.reduce((total, item) => total + item.file.size, 0);
+
+        uploadButton.disabled = uploading || waiting.length === 0;
+        queueControls.hidden = queue.length === 0;
+        queueSummary.textContent = `${waiting.length} waiting (${formatSize(waitingSize)}), ${finished.length} uploaded`;
+        clearFinishedButton.disabled = finished.length === 0;
+        clearQueueButton.disabled = uploading;
+        stopButton.hidden = !uploading;
+    }
+
+    clearFinishedButton.addEventListener("click", () => {
+        queue = queue.filter(item => item.state !== "done");
         updateFileList();
+    });
 
-        // Enable the upload button if there are files in the array
-        uploadButton.disabled = filesArray.length === 0;
+    clearQueueButton.addEventListener("click", () => {
+        queue = [];
+        updateFileList();
+        showStatus("", false);
+    });
 
-        // Clear the file input value to allow re-selecting the same files
-        fileInput.value = "";
+    // Cancels the current file and leaves the rest waiting
+    stopButton.addEventListener("click", () => {
+        stopping = true;
+        queue.filter(item => item.state === "hashing" || item.state === "uploading").forEach(cancelItem);
     });
 
     // Function to update the visual file list
     function updateFileList() {
-        // Clear the current list in the HTML
         fileList.innerHTML = "";
 
-        // Add each file to the visual list
-        filesArray.forEach((file, index) => {
+        for (const item of queue) {
             const li = document.createElement("li");
-            li.textContent = `${file.name} (${(file.size / 1024).toFixed(2)} KB)`;
-
-            // Add a remove button for each file
-            const removeButton = document.createElement("button");
-            removeButton.textContent = "Remove";
-            removeButton.style.marginLeft = "10px";
-            removeButton.addEventListener("click", () => {
-                // Remove the file from the array
-                filesArray.splice(index, 1);
-                updateFileList();
-                // Disable the upload button if no files remain
-                uploadButton.disabled = filesArray.length === 0;
-            });
+            li.className = "upload-item";
+
+            const name = document.createElement("span");
+            name.textContent = `${item.path} (${formatSize(item.file.size)})`;
+
+            const progress = document.createElement("progress");
+            progress.max = 1;
+            progress.value = item.state === "done" ? 1 : 0;
+
+            const info = document.createElement("span");
+            info.className = "upload-info";
 
-            li.appendChild(removeButton);
+            const button = document.createElement("button");
+            button.type = "button";
+            button.addEventListener("click", () => cancelItem(item));
+
+            li.append(name, progress, info, button);
             fileList.appendChild(li);
-        });
+
+            item.elements = {li, progress, info, button};
+            renderItem(item);
+        }
+
+        updateControls();
     }
 
-    // Handle form submission and send files using FormData
-    uploadForm.addEventListener("submit", (event) => {
-        event.preventDefault(); // Prevent default form submission
-        const formData = new FormData();
+    function renderItem(item, message) {
+        const {li, progress, info, button} = item.elements;
+        li.dataset.state = item.state;
+        progress.hidden = item.state === "pending" || item.state === "failed";
+        button.hidden = item.state === "done";
+        button.textContent = item.state === "hashing" || item.state === "uploading" ? "Cancel" : "Remove";
+        if (message !== undefined) {
+            info.textContent = message;
+        }
+    }
 
-        // Append each file to FormData
-        filesArray.forEach(file => {
-            formData.append("files", file);
-        });
+    // Removes a waiting file from the queue or aborts its upload
+    function cancelItem(item) {
+        if (item.state === "hashing" || item.state === "uploading") {
+            item.cancelled = true;
+            item.xhr?.abort();
+            return;
+        }
+        queue.splice(queue.indexOf(item), 1);
+        updateFileList();
+    }
+
+    // Sends one file with its checksum, reporting progress, speed and time left
+    function uploadFile(item, hash) {
+        return new Promise((resolve, reject) => {
+            const formData = new FormData();
+            // With inboxes enabled, the server needs to know the sender before the files
+            if (senderInput) {
+                formData.append("sender", senderInput.value);
+            }
+            // Files from a folder keep their place in it, e.g. photos/2024/beach.jpg
+            if (item.path.includes("/")) {
+                formData.append("path", item.path);
+            }
+            // The file is preceded by its checksum, so the server can verify what it received
+            formData.append("sha256", hash);
+            formData.append("files", item.file);
 
-        // Use Fetch API to send the form data
-        fetch("/", {
-            method: "POST", body: formData
-        })
-            .then(response => {
-                if (response.ok) {
-                    alert("Files uploaded successfully!");
-                    // Clear the file array and update the list
-                    filesArray = [];
-                    updateFileList();
-                    uploadButton.disabled = true;
+            const xhr = new XMLHttpRequest();
+            item.xhr = xhr;
+            const started = performance.now();
+
+            xhr.upload.addEventListener("progress", (event) => {
+                if (!event.lengthComputable) {
+                    return;
+                }
+                item.elements.progress.value = event.loaded / event.total;
+                const seconds = (performance.now() - started) / 1000;
+                const speed = event.loaded / Math.max(seconds, 0.001);
+                const left = (event.total - event.loaded) / Math.max(speed, 1);
+                renderItem(item, `${formatSize(speed)}/s, ${formatDuration(left)} left`);
+            });
+            xhr.addEventListener("load", () => {
+                // The server reports the outcome of every file as JSON, errors before
+                // the upload is read (e.g. the request size limit) come as plain text
+                let report = null;
+                try {
+                    report = JSON.parse(xhr.responseText);
+                } catch {
+                    // Not a report
+                }
+                const file = report?.files?.[0];
+                if (file?.status === "saved") {
+                    resolve(file);
                 } else {
-                    alert("Failed to upload files.");
+                    const message = file?.error ?? report?.error ?? xhr.responseText;
+                    reject(new Error(describeRejection(file?.code ?? xhr.status, message)));
                 }
-            })
-            .catch(error => {
-                console.error("Error:", error);
-                alert("An error occurred while uploading files.");
             });
+            xhr.addEventListener("error", () => reject(new Error("Connection to the server failed")));
+            xhr.addEventListener("abort", () => reject(new Error("Cancelled")));
+
+            xhr.open("POST", "/");
+            xhr.send(formData);
+        });
+    }
+
+    async function processItem(item) {
+        item.cancelled = false;
+        item.state = "hashing";
+        renderItem(item, "Hashing...");
+        const hash = await sha256File(item.file, (hashed) => {
+            item.elements.progress.value = hashed / Math.max(item.file.size, 1);
+            renderItem(item, `Hashing: ${Math.floor(hashed * 100 / Math.max(item.file.size, 1))}%`);
+        });
+        if (item.cancelled) {
+            throw new Error("Cancelled");
+        }
+
+        item.state = "uploading";
+        item.elements.progress.value = 0;
+        renderItem(item, "Uploading...");
+        return uploadFile(item, hash);
+    }
+
+    // Upload the queued files one by one, including the ones added meanwhile
+    uploadForm.addEventListener("submit", async (event) => {
+        event.preventDefault(); // Prevent default form submission
+
+        showStatus("", false);
+        uploading = true;
+        stopping = false;
+        updateControls();
+
+        let saved = 0;
+        let failed = 0;
+        const attempted = new Set();
+        const nextItem = () => queue.find(item =>
+            (item.state === "pending" || item.state === "failed") && !attempted.has(item));
+
+        for (let item = nextItem(); item && !stopping; item = nextItem()) {
+            attempted.add(item);
+            try {
+                const file = await processItem(item);
+                item.state = "done";
+                item.elements.progress.value = 1;
+                renderItem(item, describeSaved(file));
+                saved++;
+            } catch (error) {
+                item.state = "failed";
+                renderItem(item, error.message);
+                failed++;
+            } finally {
+                item.xhr = null;
+                updateControls();
+            }
+        }
+
+        uploading = false;
+        updateControls();
+        if (stopping) {
+            showStatus(`Stopped after ${saved} ${saved === 1 ? "file" : "files"}. Press Upload to continue.`, true);
+        } else if (failed === 0) {
+            showStatus(`${saved} ${saved === 1 ? "file" : "files"} uploaded successfully!`, false);
+        } else {
+            showStatus(`${saved} uploaded, ${failed} failed. Press Upload to retry the failed files.`, true);
+        }
+    });
+
+    // Send the text pad content, saved by the server as a .txt file
+    textForm.addEventListener("submit", async (event) => {
+        event.preventDefault();
+
+        const body = new URLSearchParams({text: textInput.value});
+        if (senderInput) {
+            if (!senderInput.reportValidity()) {
+                return;
+            }
+            body.append("sender", senderInput.value);
+        }
+
+        textButton.disabled = true;
+        try {
+            const response = await fetch("/text", {method: "POST", body});
+            if (response.ok) {
+                showStatus(await response.text(), false);
+                textInput.value = "";
+            } else {
+                showStatus(describeRejection(response.status, await response.text()), true);
+            }
+        } catch (error) {
+            console.error("Error:", error);
+            showStatus("An error occurred while sending the text.", true);
+        } finally {
+            textButton.disabled = false;
+        }
     });
 });
@@ -0,0 +1,417 @@
+document.addEventListener("DOMContentLoaded", () => {
+    const filesList = document.getElementById("files-list");
+    const filterInput = document.getElementById("filter-input");
+    const sortSelect = document.getElementById("sort-select");
+    const dirsFirst = document.getElementById("dirs-first");
+
+    const collator = new Intl.Collator(undefined, {numeric: true, sensitivity: "base"});
+
+    // Copy the text published with --text. The Clipboard API is only available on
+    // secure origins, so plain HTTP falls back to selecting the text
+    const sharedText = document.getElementById("shared-text");
+    const copyButton = document.getElementById("copy-text");
+    copyButton?.addEventListener("click", async () => {
+        try {
+            await navigator.clipboard.writeText(sharedText.textContent);
+        } catch {
+            const range = document.createRange();
+            range.selectNodeContents(sharedText);
+            const selection = window.getSelection();
+            selection.removeAllRanges();
+            selection.addRange(range);
+            document.execCommand("copy");
+        }
+        copyButton.textContent = "Copied";
+        setTimeout(() => copyButton.textContent = "Copy", 1500);
+    });
+
+    // Compare two list items according to the selected sort controls
+    function compareItems(a, b) {
+        if (dirsFirst.checked) {
+            const dirsOrder = b.hasAttribute("data-dir") - a.hasAttribute("data-dir");
+            if (dirsOrder !== 0) {
+                return dirsOrder;
+            }
+        }
+
+        const byName = collator.compare(a.dataset.name, b.dataset.name);
+        switch (sortSelect.value) {
+            case "size":
+                return (Number(b.dataset.size) - Number(a.dataset.size)) || byName;
+            case "mtime":
+                return (Number(b.dataset.mtime) - Number(a.dataset.mtime)) || byName;
+            default:
+                return byName;
+        }
+    }
+
+    // Reorder the items of every list in the tree
+    function sortLists() {
+        filesList.querySelectorAll("ul").forEach(ul => {
+            const items = Array.from(ul.children).filter(li => li.tagName === "LI");
+            items.sort(compareItems).forEach(li => ul.appendChild(li));
+        });
+    }
+
+    // Hide items that don't match the filter; keep folders with matching descendants visible
+    function filterItem(li, query) {
+        const nestedList = li.querySelector(":scope > details > ul");
+        let nestedMatch = false;
+        if (nestedList) {
+            for (const child of nestedList.children) {
+                nestedMatch = filterItem(child, query) || nestedMatch;
+            }
+        }
+
+        const selfMatch = li.dataset.name.toLowerCase().includes(query);
+        const visible = query === "" || selfMatch || nestedMatch;
+        li.hidden = !visible;
+
+        const details = li.querySelector(":scope > details");
+        if (details && query !== "") {
+            details.open = nestedMatch;
+        }
+
+        // A matching folder shows its whole content
+        if (selfMatch && nestedList && query !== "") {
+            nestedList.querySelectorAll("li").forEach(nested => nested.hidden = false);
+        }
+
+        return visible;
+    }
+
+    function applyFilter() {
+        const query = filterInput.value.trim().toLowerCase();
+        const rootList = filesList.querySelector(":scope > ul");
+        if (rootList) {
+            for (const li of rootList.children) {
+                filterItem(li, query);
+            }
+        }
+    }
+
+    sortSelect.addEventListener("change", sortLists);
+    dirsFirst.addEventListener("change", sortLists);
+    filterInput.addEventListener("input", applyFilter);
+});
+
+document.addEventListener("DOMContentLoaded", () => {
+    const searchForm = document.getElementById("search-form");
+    const searchInput = document.getElementById("search-input");
+    const searchMode = document.getElementById("search-mode");
+    const searchScope = document.getElementById("search-scope");
+    const searchResults = document.getElementById("search-results");
+
+    function link(href, text) {
+        const a = document.createElement("a");
+        a.href = href;
+        a.textContent = text;
+        return a;
+    }
+
+    function renderResults(data) {
+        searchResults.innerHTML = "";
+        const ul = document.createElement("ul");
+
+        for (const result of data.results) {
+            const li = document.createElement("li");
+            if (result.is_dir) {
+                li.append("📁 " + result.path);
+            } else {
+                li.append("🗋 ");
+                li.append(result.download ? link(result.download, result.path) : result.path);
+                if (result.preview) {
+                    li.append(" ", link(result.preview, "[view]"));
+                }
+            }
+            ul.appendChild(li);
+        }
+
+        const summary = document.createElement("p");
+        summary.textContent = data.results.length === 0
+            ? "Nothing found."
+            : `Found ${data.results.length}${data.truncated ? "+" : ""} entries`;
+
+        searchResults.append(summary, ul);
+        searchResults.hidden = false;
+    }
+
+    function renderFileMatches(ul, fileMatches) {
+        const li = document.createElement("li");
+        li.append("🗋 ", link(fileMatches.download, fileMatches.path));
+
+        for (const match of fileMatches.matches) {
+            const pre = document.createElement("pre");
+            pre.className = "grep-match";
+            const firstLine = match.line - match.before.length;
+            match.before.forEach((line, i) => pre.append(`${firstLine + i}- ${line}\n`));
+            const hit = document.createElement("strong");
+            hit.textContent = `${match.line}: ${match.text}\n`;
+            pre.append(hit);
+            match.after.forEach((line, i) => pre.append(`${match.line + 1 + i}- ${line}\n`));
+            li.appendChild(pre);
+        }
+
+        ul.appendChild(li);
+    }
+
+    // Read the NDJSON stream and render every file as soon as it arrives
+    async function searchContents(params) {
+        const response = await fetch(`/grep?${params}`);
+        if (!response.ok) {
+            throw new Error(await response.text());
+        }
+
+        searchResults.innerHTML = "";
+        const summary = document.createElement("p");
+        summary.textContent = "Searching...";
+        const ul = document.createElement("ul");
+        searchResults.append(summary, ul);
+        searchResults.hidden = false;
+
+        const reader = response.body.pipeThrough(new TextDecoderStream()).getReader();
+        let buffer = "";
+        let files = 0;
+        for (; ;) {
+            const {value, done} = await reader.read();
+            if (done) {
+                break;
+            }
+            buffer += value;
+            const lines = buffer.split("\n");
+            buffer = lines.pop();
+            for (const line of lines.filter(line => line !== "")) {
+                renderFileMatches(ul, JSON.parse(line));
+                files += 1;
+            }
+        }
+
+        summary.textContent = files === 0 ? "Nothing found." : `Found matches in ${files} files`;
+    }
+
+    searchForm.addEventListener("submit", (event) => {
+        event.preventDefault();
+        const query = searchInput.value.trim();
+        if (query === "") {
+            searchResults.hidden = true;
+            return;
+        }
+
+        if (searchScope.value === "content") {
+            searchContents(new URLSearchParams({q: query, mode: searchMode.value}))
+                .catch(error => {
+                    searchResults.textContent = `Search failed: ${error.message}`;
+                    searchResults.hidden = false;
+                });
+            return;
+        }
+
+        const params = new URLSearchParams({q: query, mode: searchMode.value, scope: searchScope.value});
+        fetch(`/search?${params}`)
+            .then(async response => {
+                if (!response.ok) {
+                    throw new Error(await response.text());
+                }
+                return response.json();
+            })
+            .then(renderResults)
+            .catch(error => {
+                searchResults.textContent = `Search failed: ${error.message}`;
+                searchResults.hidden = false;
+            });
+    });
+});
+
+document.addEventListener("DOMContentLoaded", () => {
+    const SLIDESHOW_INTERVAL_MS = 3000;
+
+    const lightbox = document.createElement("div");
+    lightbox.id = "lightbox";
+    lightbox.hidden = true;
+    lightbox.innerHTML = `
+        <img alt="">
+        <p class="lightbox-caption"></p>
+        <div class="button-group">
+            <button type="button" data-action="prev">Prev</button>
+            <button type="button" data-action="slideshow">Play</button>
+            <button type="button" data-action="next">Next</button>
+            <button type="button" data-action="close">Close</button>
+        </div>`;
+    document.body.appendChild(lightbox);
+
+    const image = lightbox.querySelector("img");
+    const caption = lightbox.querySelector(".lightbox-caption");
+    const slideshowButton = lightbox.querySelector("[data-action=slideshow]");
+
+    let links = [];
+    let current = 0;
+    let slideshowTimer = null;
+
+    function show(index) {
+        current = (index + links.length) % links.length;
+        image.src = links[current].href;
+        caption.textContent = `${links[current].title} `;
+        const hexLink = document.createElement("a");
+        hexLink.href = `${links[current].href}&mode=hex`;
+        hexLink.textContent = "[hex]";
+        caption.appendChild(hexLink);
+    }
+
+    function stopSlideshow() {
+        clearInterval(slideshowTimer);
+        slideshowTimer = null;
+        slideshowButton.textContent = "Play";
+    }
+
+    function toggleSlideshow() {
+        if (slideshowTimer) {
+            stopSlideshow();
+        } else {
+            slideshowTimer = setInterval(() => show(current + 1), SLIDESHOW_INTERVAL_MS);
+            slideshowButton.textContent = "Pause";
+        }
+    }
+
+    function close() {
+        stopSlideshow();
+        lightbox.hidden = true;
+        image.removeAttribute("src");
+    }
+
+    // Open the lightbox with the visible images of the same gallery
+    document.addEventListener("click", (event) => {
+        const link = event.target.closest(".lightbox-link");
+        if (!link) {
+            return;
+        }
+        event.preventDefault();
+
+        const gallery = link.closest("ul.gallery");
+        links = Array.from(gallery.querySelectorAll(":scope > li:not([hidden]) > .lightbox-link"));
+        show(links.indexOf(link));
+        lightbox.hidden = false;
+    });
+
+    lightbox.addEventListener("click", (event) => {
+        switch (event.target.dataset.action) {
+            case "prev":
+                show(current - 1);
+                break;
+            case "next":
+                show(current + 1);
+                break;
+            case "slideshow":
+                toggleSlideshow();
+                break;
+            case "close":
+                close();
+                break;
+        }
+    });
+
+    document.addEventListener("keydown", (event) => {
+        if (lightbox.hidden) {
+            return;
+        }
+        if (event.key === "ArrowLeft") {
+            show(current - 1);
+        } else if (event.key === "ArrowRight") {
+            show(current + 1);
+        } else if (event.key === "Escape") {
+            close();
+        }
+    });
+});
+
+document.addEventListener("DOMContentLoaded", () => {
+    // Builds nested lists from the flat member paths of an archive
+    function buildTree(archiveId, entries) {
+        const root = {children: new Map()};
+        for (const entry of entries) {
+            let node = root;
+            const parts = entry.path.split("/").filter(part => part !== "");
+            parts.forEach((part, i) => {
+                if (!node.children.has(part)) {
+                    node.children.set(part, {children: new Map(), entry: null});
+                }
+                node = node.children.get(part);
+                if (i === parts.length - 1) {
+                    node.entry = entry;
+                }
+            });
+        }
+        return renderNode(archiveId, root);
+    }
+
+    function renderNode(archiveId, node) {
+        const ul = document.createElement("ul");
+        for (const [name, child] of node.children) {
+            const li = document.createElement("li");
+            const isDir = child.children.size > 0 || (child.entry && child.entry.is_dir);
+            if (isDir) {
+                const details = document.createElement("details");
+                const summary = document.createElement("summary");
+                summary.textContent = `📁 ${name}`;
+                details.append(summary, renderNode(archiveId, child));
+                li.appendChild(details);
+            } else {
+                const params = new URLSearchParams({id: archiveId, path: child.entry.path});
+                const download = document.createElement("a");
+                download.href = `/archive/member?${params}`;
+                download.textContent = name;
+                const view = document.createElement("a");
+                view.href = `/archive/member?${params}&inline=true`;
+                view.textContent = "[view]";
+                li.append("🗋 ", download, `, ${formatSize(child.entry.size)} `, view);
+            }
+            ul.appendChild(li);
+        }
+        return ul;
+    }
+
+    function formatSize(bytes) {
+        const units = ["KiB", "MiB", "GiB", "TiB"];
+        if (bytes < 1024) {
+            return `${bytes} B`;
+        }
+        let size = bytes / 1024;
+        let unit = 0;
+        while (size >= 1024 && unit < units.length - 1) {
+            size /= 1024;
+            unit += 1;
+        }
+        return `${size.toFixed(1)} ${units[unit]}`;
+    }
+
+    // Load the member list the first time an archive is expanded
+    document.addEventListener("toggle", (event) => {
+        const details = event.target;
+        if (!details.classList || !details.classList.contains("archive") || !details.open) {
+            return;
+        }
+        if (details.dataset.loaded) {
+            return;
+        }
+        details.dataset.loaded = "true";
+
+        const members = details.querySelector(".archive-members");
+        members.textContent = "Loading...";
+        fetch(`/archive?id=${details.dataset.archiveId}`)
+            .then(response => {
+                if (!response.ok) {
+                    throw new Error(`HTTP ${response.status}`);
+                }
+                return response.json();
+            })
+            .then(listing => {
+                members.replaceChildren(buildTree(details.dataset.archiveId, listing.entries));
+                if (listing.truncated) {
+                    members.append("… (the list is truncated)");
+                }
+            })
+            .catch(error => {
+                members.textContent = `Failed to read the archive: ${error.message}`;
+                delete details.dataset.loaded;
+            });
+    }, true);
+});